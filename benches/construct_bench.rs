@@ -0,0 +1,104 @@
+//! Cost of constructing a linked `Astruct` three ways:
+//!
+//! - `box`: `Box::pin(Astruct::new(..))` then `link()`, no unsafe.
+//! - `new_uninit`: `Astruct::boxed_linked`, fields and link written into
+//!   the `Box::new_uninit` allocation.
+//! - `new_zeroed`: `Astruct::boxed_zeroed` then set the fields and link.
+//!
+//! Timed with `Instant` like `reset_bench`: `cargo bench --bench
+//! construct_bench`. Each is run once per construction and batched,
+//! collecting `BATCH` values into a preallocated `Vec`.
+//!
+//! On an x86_64 Linux box one run gave, per construction:
+//!
+//! ```text
+//!              single  batched
+//! box             1ns     10ns
+//! new_uninit      1ns     10ns
+//! new_zeroed     22ns     13ns
+//! ```
+//!
+//! Singly the optimizer removes the allocate/free pair for `box` and
+//! `new_uninit`, so only batched reflects a real allocation: `new_uninit`
+//! costs the same as `Box::new`, the unsafe path buys a value that is
+//! never observable unlinked, not speed. Zeroing goes through
+//! `alloc_zeroed` instead, which is slower and isn't elided.
+
+use std::hint::black_box;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use expr_builder_pattern::Astruct;
+
+const ITERATIONS: u32 = 1_000_000;
+const BATCH: u32 = 10_000;
+
+type Construct = fn(u32) -> Pin<Box<Astruct>>;
+
+fn via_box(i: u32) -> Pin<Box<Astruct>> {
+    let mut a = Box::pin(Astruct::new(1, i));
+    a.as_mut().link();
+    a
+}
+
+fn via_new_uninit(i: u32) -> Pin<Box<Astruct>> {
+    Astruct::boxed_linked(1, i)
+}
+
+fn via_new_zeroed(i: u32) -> Pin<Box<Astruct>> {
+    let mut a = Box::into_pin(Astruct::boxed_zeroed());
+    *a.as_mut().a_u8_mut() = 1;
+    a.as_mut().set_a_u32(i);
+    a.as_mut().link();
+    a
+}
+
+fn report(name: &str, elapsed: Duration, count: u32) {
+    println!(
+        "{name:>18}: {:?} total, {:?}/construction",
+        elapsed,
+        elapsed / count
+    );
+}
+
+fn single(name: &str, f: Construct) {
+    let start = Instant::now();
+    let mut sum = 0u32;
+    for i in 0..ITERATIONS {
+        let a = f(black_box(i));
+        sum = sum.wrapping_add(a.referenced_u32().unwrap());
+    }
+    black_box(sum);
+    report(name, start.elapsed(), ITERATIONS);
+}
+
+fn batched(name: &str, f: Construct) {
+    let mut v = Vec::with_capacity(BATCH as usize);
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..ITERATIONS / BATCH {
+        let start = Instant::now();
+        for i in 0..BATCH {
+            v.push(f(black_box(i)));
+        }
+        elapsed += start.elapsed();
+        black_box(&v);
+        // Dropping is not part of construction
+        v.clear();
+    }
+    report(&format!("{name} x{BATCH}"), elapsed, ITERATIONS);
+}
+
+fn main() {
+    let ways: [(&str, Construct); 3] = [
+        ("box", via_box),
+        ("new_uninit", via_new_uninit),
+        ("new_zeroed", via_new_zeroed),
+    ];
+    for (name, f) in ways {
+        assert!(f(1).validate().is_ok());
+        single(name, f);
+    }
+    for (name, f) in ways {
+        batched(name, f);
+    }
+}
@@ -0,0 +1,57 @@
+//! Building in a loop with a fresh `AstructBuilder` per iteration versus
+//! one builder handed back by `build_and_reset()` each time.
+//!
+//! Timed with `Instant` so it runs on stable without extra dependencies:
+//! `cargo bench --bench reset_bench`. The builder is two `Option`s on the
+//! stack either way, so the boxed build is expected to dominate and both
+//! to come out about even.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use expr_builder_pattern::AstructBuilder;
+
+const ITERATIONS: u32 = 1_000_000;
+
+// `run` does all ITERATIONS itself so it can keep state across them
+fn time(name: &str, run: impl FnOnce() -> u32) -> Duration {
+    let start = Instant::now();
+    black_box(run());
+    let elapsed = start.elapsed();
+    println!(
+        "{name:>8}: {:?} total, {:?}/iter",
+        elapsed,
+        elapsed / ITERATIONS
+    );
+    elapsed
+}
+
+fn main() {
+    time("fresh", || {
+        let mut sum = 0u32;
+        for i in 0..ITERATIONS {
+            let a = AstructBuilder::new()
+                .set_a_u8(1)
+                .set_a_u32(black_box(i))
+                .link()
+                .build();
+            sum = sum.wrapping_add(black_box(a).a_u32);
+        }
+        sum
+    });
+
+    time("reset", || {
+        let mut sum = 0u32;
+        let mut b = AstructBuilder::new();
+        for i in 0..ITERATIONS {
+            let (a, reset) = b
+                .set_a_u8(1)
+                .set_a_u32(black_box(i))
+                .link()
+                .build_and_reset();
+            sum = sum.wrapping_add(black_box(a).a_u32);
+            b = reset;
+        }
+        sum
+    });
+}
@@ -0,0 +1,429 @@
+//! Expansion of `#[derive(Builder)]`.
+
+use proc_macro::TokenStream;
+
+use crate::parse::{angle, Field, Struct};
+
+const CRATE: &str = "::expr_builder_pattern";
+
+/// Marker fields get no setter and are always `Default::default()`.
+fn is_marker(f: &Field) -> bool {
+    let ty = f.ty.replace(' ', "");
+    let last = ty.split('<').next().unwrap_or("").rsplit("::").next();
+    matches!(last, Some("PhantomData" | "PhantomPinned"))
+}
+
+/// The type inside `Option<...>`, also spelled `core::option::Option` or
+/// `std::option::Option` with or without a leading `::`, or the type
+/// itself. `ty` has no spaces.
+fn strip_option(ty: &str) -> &str {
+    let path = ty.strip_prefix("::").unwrap_or(ty);
+    let path = ["core::option::", "std::option::"]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .unwrap_or(path);
+    path.strip_prefix("Option<")
+        .and_then(|t| t.strip_suffix('>'))
+        .unwrap_or(ty)
+}
+
+/// `&T` or `Option<&T>`, whose lifetime `build()` can't tie to the
+/// allocation it points into.
+fn is_reference(f: &Field) -> bool {
+    strip_option(&f.ty.replace(' ', "")).starts_with('&')
+}
+
+/// The types with a `SelfRefLink` impl: `NonNull<T>`, `Option<NonNull<T>>`,
+/// `*const T` and `*mut T`.
+fn is_link_pointer(f: &Field) -> bool {
+    let ty = f.ty.replace(' ', "");
+    if ty.starts_with("*const") || ty.starts_with("*mut") {
+        return true;
+    }
+    let inner = strip_option(&ty);
+    let last = inner.split('<').next().unwrap_or("").rsplit("::").next();
+    last == Some("NonNull")
+}
+
+pub fn expand(s: &Struct) -> Result<TokenStream, String> {
+    let vis = &s.vis;
+    let name = &s.name;
+    let builder = format!("{}Builder", name);
+    let impl_generics = s.generics.impl_generics();
+    let ty_generics = s.generics.ty_generics();
+    let ty = format!("{}{}", name, ty_generics);
+    let where_clause = s.generics.where_clause();
+
+    // The builder gets a trailing mode parameter
+    let mode = format!("{CRATE}::builder_mode");
+    let with = |extra: String| {
+        let mut params = s.generics.params.clone();
+        params.push(extra);
+        params
+    };
+    let decl_generics = angle(&with(format!("M: {mode}::BuildMode = {mode}::Consuming")));
+    let b_impl_generics = angle(&with(format!("M: {mode}::BuildMode")));
+    let b_ty = |m: &str| {
+        let mut args = s.generics.args.clone();
+        args.push(m.to_string());
+        format!("{}{}", builder, angle(&args))
+    };
+    let (b_any, b_consuming, b_mutable) = (
+        b_ty("M"),
+        b_ty(&format!("{mode}::Consuming")),
+        b_ty(&format!("{mode}::Mutable")),
+    );
+
+    let settable: Vec<&Field> = s
+        .fields
+        .iter()
+        .filter(|f| f.attrs.self_ref.is_none() && !is_marker(f))
+        .collect();
+
+    let lazies: Vec<&Field> = settable
+        .iter()
+        .copied()
+        .filter(|f| f.attrs.lazy_self_ref)
+        .collect();
+    if let Some(f) = lazies.iter().find(|f| f.attrs.optional) {
+        return Err(format!(
+            "`{}` can't be both `optional` and `lazy_default`",
+            f.name
+        ));
+    }
+    if let Some(f) = s.fields.iter().find(|f| {
+        f.attrs.self_ref.is_some() && (f.attrs.map.is_some() || f.attrs.validate.is_some())
+    }) {
+        return Err(format!("`{}` has no setter to `map` or `validate`", f.name));
+    }
+    if let Some(f) = s
+        .fields
+        .iter()
+        .find(|f| f.attrs.self_ref.is_some() && !is_link_pointer(f))
+    {
+        return Err(format!(
+            "self_ref field `{}` must be a `NonNull` or raw pointer, a reference \
+            could outlive the allocation it points into",
+            f.name
+        ));
+    }
+    if let Some(f) = lazies.iter().find(|f| is_reference(f)) {
+        return Err(format!(
+            "lazy_default field `{}` can't be a reference, use a `NonNull` or raw \
+            pointer that can't outlive the allocation it points into",
+            f.name
+        ));
+    }
+    let lazy_ty = |f: &Field| format!("fn(*const {ty}) -> {}", f.ty);
+
+    let mut links = Vec::new();
+    for f in s.fields.iter().filter(|f| f.attrs.self_ref.is_some()) {
+        let target = f.attrs.self_ref.as_ref().unwrap();
+        let Some(t) = settable.iter().find(|t| &t.name == target) else {
+            return Err(format!(
+                "self_ref target `{}` of `{}` must be a settable field",
+                target, f.name
+            ));
+        };
+        links.push((f, *t));
+    }
+
+    let storage: String = settable
+        .iter()
+        .map(|f| format!("{}: ::core::option::Option<{}>,", f.name, f.ty))
+        .chain(
+            lazies
+                .iter()
+                .map(|f| format!("{}_lazy: ::core::option::Option<{}>,", f.name, lazy_ty(f))),
+        )
+        .collect();
+    let nones: String = settable
+        .iter()
+        .map(|f| f.name.clone())
+        .chain(lazies.iter().map(|f| format!("{}_lazy", f.name)))
+        .map(|n| format!("{}: ::core::option::Option::None,", n))
+        .collect();
+    let consuming_setters: String = settable
+        .iter()
+        .map(|f| {
+            format!(
+                "#[inline] pub fn set_{n}(mut self, {n}: {t}) -> Self {{ \
+                    self.{n} = ::core::option::Option::Some({v}); self \
+                }}",
+                n = f.name,
+                t = f.ty,
+                v = stored_value(f),
+            )
+        })
+        .chain(
+            settable
+                .iter()
+                .filter_map(|f| try_setter(f, "mut self", "Self")),
+        )
+        .chain(lazies.iter().map(|f| {
+            format!(
+                "#[doc = \"Compute `{n}` from the value being built when it is not set.\"] \
+                #[inline] pub fn set_{n}_lazy(mut self, {n}: {t}) -> Self {{ \
+                    self.{n}_lazy = ::core::option::Option::Some({n}); self \
+                }}",
+                n = f.name,
+                t = lazy_ty(f),
+            )
+        }))
+        .collect();
+    let mutable_setters: String = settable
+        .iter()
+        .map(|f| {
+            format!(
+                "#[inline] pub fn set_{n}(&mut self, {n}: {t}) -> &mut Self {{ \
+                    self.{n} = ::core::option::Option::Some({v}); self \
+                }}",
+                n = f.name,
+                t = f.ty,
+                v = stored_value(f),
+            )
+        })
+        .chain(
+            settable
+                .iter()
+                .filter_map(|f| try_setter(f, "&mut self", "&mut Self")),
+        )
+        .chain(lazies.iter().map(|f| {
+            format!(
+                "#[doc = \"Compute `{n}` from the value being built when it is not set.\"] \
+                #[inline] pub fn set_{n}_lazy(&mut self, {n}: {t}) -> &mut Self {{ \
+                    self.{n}_lazy = ::core::option::Option::Some({n}); self \
+                }}",
+                n = f.name,
+                t = lazy_ty(f),
+            )
+        }))
+        .collect();
+    let resets: String = settable
+        .iter()
+        .map(|f| f.name.clone())
+        .chain(lazies.iter().map(|f| format!("{}_lazy", f.name)))
+        .map(|n| format!("self.{} = ::core::option::Option::None;", n))
+        .collect();
+    let moves: String = settable
+        .iter()
+        .map(|f| f.name.clone())
+        .chain(lazies.iter().map(|f| format!("{}_lazy", f.name)))
+        .map(|n| format!("{n}: self.{n},"))
+        .collect();
+    // Every missing field is recorded before any of them is unwrapped
+    let missing = |n: &str| {
+        format!(
+            "{{ errors.push({CRATE}::errors::BuildError::MissingField {{ name: {n:?} }}); \
+                ::core::option::Option::None }}"
+        )
+    };
+    let checks: String = settable
+        .iter()
+        .map(|f| {
+            let n = &f.name;
+            if f.attrs.optional {
+                format!("let {n} = ::core::option::Option::Some(self.{n}.unwrap_or_default());")
+            } else if f.attrs.lazy_self_ref {
+                // Resolved to the value or the function computing it
+                format!(
+                    "let {n} = match (self.{n}, self.{n}_lazy) {{ \
+                        (::core::option::Option::Some(v), _) => \
+                            ::core::option::Option::Some(::core::result::Result::Ok(v)), \
+                        (::core::option::Option::None, ::core::option::Option::Some(f)) => \
+                            ::core::option::Option::Some(::core::result::Result::Err(f)), \
+                        (::core::option::Option::None, ::core::option::Option::None) => {m}, \
+                    }};",
+                    m = missing(n)
+                )
+            } else {
+                format!(
+                    "let {n} = match self.{n} {{ \
+                        ::core::option::Option::Some(v) => ::core::option::Option::Some(v), \
+                        ::core::option::Option::None => {m}, \
+                    }};",
+                    m = missing(n)
+                )
+            }
+        })
+        .collect();
+    let unwraps: String = settable
+        .iter()
+        .map(|f| {
+            format!(
+                "let {n} = match {n} {{ \
+                    ::core::option::Option::Some(v) => v, \
+                    ::core::option::Option::None => ::core::unreachable!(), \
+                }};",
+                n = f.name
+            )
+        })
+        .collect();
+    let values = format!(
+        "let mut errors = {CRATE}::__private::Vec::new(); \
+        {checks} \
+        if !errors.is_empty() {{ return ::core::result::Result::Err(errors); }} \
+        {unwraps}"
+    );
+
+    let (build_doc, built_ty, construct) = if links.is_empty() && lazies.is_empty() {
+        let inits: String = s
+            .fields
+            .iter()
+            .map(|f| {
+                if is_marker(f) {
+                    format!("{}: ::core::default::Default::default(),", f.name)
+                } else {
+                    format!("{n}: {n},", n = f.name)
+                }
+            })
+            .collect();
+        (
+            "Build the value, failing on the first required field that was not set.",
+            ty.clone(),
+            format!("::core::result::Result::Ok({name} {{ {inits} }})"),
+        )
+    } else {
+        let writes: String = s
+            .fields
+            .iter()
+            .filter(|f| f.attrs.self_ref.is_none() && !f.attrs.lazy_self_ref)
+            .map(|f| {
+                let v = if is_marker(f) {
+                    "::core::default::Default::default()".to_string()
+                } else {
+                    f.name.clone()
+                };
+                format!("::core::ptr::addr_of_mut!((*p).{}).write({});", f.name, v)
+            })
+            .collect();
+        let link_writes: String = links
+            .iter()
+            .map(|(f, t)| {
+                format!(
+                    "::core::ptr::addr_of_mut!((*p).{n}).write(\
+                        <{fty} as {CRATE}::self_ref::SelfRefLink<{tty}>>::link(\
+                            ::core::ptr::addr_of_mut!((*p).{t})));",
+                    n = f.name,
+                    fty = f.ty,
+                    t = t.name,
+                    tty = t.ty,
+                )
+            })
+            .chain(lazies.iter().map(|f| {
+                format!(
+                    "::core::ptr::addr_of_mut!((*p).{n}).write(match {n} {{ \
+                        ::core::result::Result::Ok(v) => v, \
+                        ::core::result::Result::Err(f) => f(p), \
+                    }});",
+                    n = f.name
+                )
+            }))
+            .collect();
+        // Value fields are written in `values`, links only after their
+        // targets and lazy defaults once every value field can be read.
+        (
+            "Build the value in a heap allocation with every `self_ref` field \
+            pointing at its target and unset `lazy_default` fields computed from \
+            it, failing on the first required field that was not set. It is \
+            boxed since moving it would break the links.",
+            format!("{CRATE}::__private::Box<{ty}>"),
+            format!(
+                "let built = unsafe {{ \
+                    {CRATE}::self_ref::SelfRefBuilder::<{ty}>::new().build(\
+                        |p| {{ {writes} }}, \
+                        |p| {{ {link_writes} }}, \
+                    ) \
+                }}; \
+                ::core::result::Result::Ok(built)"
+            ),
+        )
+    };
+    let build = format!(
+        "#[doc = \"Like [`build`](Self::build) but reporting every required field \
+        that was not set.\"] \
+        pub fn build_collecting_errors(self) -> ::core::result::Result<\
+            {built_ty}, {CRATE}::__private::Vec<{CRATE}::errors::BuildError>> {{ \
+            {values} \
+            {construct} \
+        }} \
+        #[doc = {build_doc:?}] \
+        pub fn build(self) -> ::core::result::Result<{built_ty}, {CRATE}::errors::BuildError> {{ \
+            self.build_collecting_errors().map_err(|errors| match errors.into_iter().next() {{ \
+                ::core::option::Option::Some(e) => e, \
+                ::core::option::Option::None => ::core::unreachable!(), \
+            }}) \
+        }}"
+    );
+
+    let out = format!(
+        "#[doc = \"Builder for [`{name}`] generated by `#[derive(Builder)]`.\"] \
+        {vis} struct {builder} {decl_generics} {where_clause} {{ \
+            {storage} \
+            _marker: ::core::marker::PhantomData<fn() -> ({ty}, M)>, \
+        }} \
+        impl {b_impl_generics} ::core::default::Default for {b_any} {where_clause} {{ \
+            fn default() -> Self {{ {builder} {{ {nones} _marker: ::core::marker::PhantomData }} }} \
+        }} \
+        impl {impl_generics} {b_consuming} {where_clause} {{ \
+            #[inline] pub fn new() -> Self {{ ::core::default::Default::default() }} \
+            {consuming_setters} \
+            #[doc = \"Switch to setters taking `&mut self`.\"] \
+            #[inline] pub fn into_mutable(self) -> {b_mutable} {{ \
+                {builder} {{ {moves} _marker: ::core::marker::PhantomData }} \
+            }} \
+        }} \
+        impl {impl_generics} {b_mutable} {where_clause} {{ \
+            {mutable_setters} \
+            #[doc = \"Switch to setters taking and returning `self`.\"] \
+            #[inline] pub fn into_consuming(self) -> {b_consuming} {{ \
+                {builder} {{ {moves} _marker: ::core::marker::PhantomData }} \
+            }} \
+        }} \
+        impl {b_impl_generics} {b_any} {where_clause} {{ \
+            #[doc = \"Clear every field, including lazy defaults, to reuse the builder.\"] \
+            #[inline] pub fn reset(&mut self) {{ {resets} }} \
+            {build} \
+        }}"
+    );
+    out.parse()
+        .map_err(|e| format!("derive(Builder) generated invalid code: {:?}", e))
+}
+
+/// What `set_<field>` stores, the argument passed through `map` if given.
+fn stored_value(f: &Field) -> String {
+    match &f.attrs.map {
+        Some(path) => format!(
+            "::expr_builder_pattern::FieldMapper::map(&{}, {})",
+            path, f.name
+        ),
+        None => f.name.clone(),
+    }
+}
+
+/// `try_set_<field>` for a field with `validate = path`, storing like
+/// `set_<field>` once `path` accepted the value.
+fn try_setter(f: &Field, receiver: &str, ret: &str) -> Option<String> {
+    let path = f.attrs.validate.as_ref()?;
+    Some(format!(
+        "#[doc = \"`set_{n}` returning the error of `{path}` instead of storing a rejected value.\"] \
+        pub fn try_set_{n}({receiver}, {n}: {t}) \
+            -> ::core::result::Result<{ret}, ::expr_builder_pattern::ValidationError> {{ \
+            let {n}: {t} = {v}; \
+            match {path}({n}) {{ \
+                ::core::result::Result::Ok(()) => {{ \
+                    self.{n} = ::core::option::Option::Some({n}); \
+                    ::core::result::Result::Ok(self) \
+                }} \
+                ::core::result::Result::Err(msg) => \
+                    ::core::result::Result::Err(::expr_builder_pattern::ValidationError {{ \
+                        field_name: \"{n}\", \
+                        message: ::expr_builder_pattern::__private::String::from(msg), \
+                    }}), \
+            }} \
+        }}",
+        n = f.name,
+        t = f.ty,
+        v = stored_value(f),
+    ))
+}
@@ -0,0 +1,75 @@
+//! Expansion of `#[derive(EnumBuilder)]`.
+
+use proc_macro::TokenStream;
+
+use crate::parse::Enum;
+
+const CRATE: &str = "::expr_builder_pattern";
+
+/// `SmallThing` to `small_thing`.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn expand(e: &Enum) -> Result<TokenStream, String> {
+    let vis = &e.vis;
+    let name = &e.name;
+    let builder = format!("{}Builder", name);
+    let impl_generics = e.generics.impl_generics();
+    let ty_generics = e.generics.ty_generics();
+    let ty = format!("{}{}", name, ty_generics);
+    let where_clause = e.generics.where_clause();
+
+    let setters: String = e
+        .variants
+        .iter()
+        .map(|v| {
+            let (param, value) = match &v.ty {
+                Some(t) => (
+                    format!(", value: {}", t),
+                    format!("{}::{}(value)", name, v.name),
+                ),
+                None => (String::new(), format!("{}::{}", name, v.name)),
+            };
+            format!(
+                "#[doc = \"Select `{name}::{variant}`, replacing any earlier choice.\"] \
+                #[inline] pub fn set_{snake}(mut self{param}) -> Self {{ \
+                    self.variant = ::core::option::Option::Some({value}); self \
+                }}",
+                variant = v.name,
+                snake = snake_case(&v.name),
+            )
+        })
+        .collect();
+
+    let out = format!(
+        "#[doc = \"Builder for [`{name}`] generated by `#[derive(EnumBuilder)]`.\"] \
+        {vis} struct {builder} {impl_generics} {where_clause} {{ \
+            variant: ::core::option::Option<{ty}>, \
+        }} \
+        impl {impl_generics} ::core::default::Default for {builder}{ty_generics} {where_clause} {{ \
+            fn default() -> Self {{ {builder} {{ variant: ::core::option::Option::None }} }} \
+        }} \
+        impl {impl_generics} {builder}{ty_generics} {where_clause} {{ \
+            #[inline] pub fn new() -> Self {{ ::core::default::Default::default() }} \
+            {setters} \
+            #[doc = \"The selected variant, `NoVariantSelected` if none was.\"] \
+            pub fn build(self) -> ::core::result::Result<{ty}, {CRATE}::errors::BuildError> {{ \
+                self.variant.ok_or({CRATE}::errors::BuildError::NoVariantSelected) \
+            }} \
+        }}"
+    );
+    out.parse()
+        .map_err(|e| format!("derive(EnumBuilder) generated invalid code: {:?}", e))
+}
@@ -0,0 +1,70 @@
+//! Derive macros for `expr-builder-pattern`.
+//!
+//! The macros parse the item directly from `proc_macro` tokens and emit
+//! code referring to `::expr_builder_pattern`, so they are meant to be
+//! used through that crate's re-exports.
+
+use proc_macro::TokenStream;
+
+mod builder;
+mod enum_builder;
+mod parse;
+mod uninit_drop;
+
+/// Implements `UninitDrop` and one `FieldAt<IDX>` per field so the struct
+/// can be initialized field by field through an `UninitBox`. Private
+/// fields get a `FieldAt` too, which is why `UninitBox::write_field` is
+/// `unsafe`.
+#[proc_macro_derive(UninitDrop)]
+pub fn derive_uninit_drop(input: TokenStream) -> TokenStream {
+    match parse::parse_struct(input) {
+        Ok(s) => uninit_drop::expand(&s),
+        Err(msg) => parse::compile_error(&msg),
+    }
+}
+
+/// Generates `<Name>Builder` with an `Option` per field, a `set_<field>`
+/// chaining setter per field and `build()` returning
+/// `Result<_, BuildError>`. `build_collecting_errors()` returns every
+/// missing field as `Vec<BuildError>` instead of just the first.
+///
+/// The builder takes a trailing `M: BuildMode` parameter defaulting to
+/// `Consuming`, see `expr_builder_pattern::builder_mode`.
+///
+/// Field options:
+/// - `#[builder(optional)]`: falls back to `Default::default()` instead of
+///   `BuildError::MissingField`.
+/// - `#[builder(self_ref(target = "field"))]`: no setter, `build()` points
+///   it at `field` inside a `Box::new_uninit` allocation and returns the
+///   value boxed. `#[self_ref(points_to = "field")]` is the same. The field
+///   must be a `NonNull<T>`, `Option<NonNull<T>>`, `*const T` or `*mut T`.
+/// - `#[builder(lazy_default = "self_ref")]`: besides `set_<field>` there is
+///   `set_<field>_lazy(fn(*const Name) -> Field)`, run inside `build()` on
+///   the boxed allocation once every value field is written when the field
+///   itself was not set. The value is returned boxed here as well. The
+///   field can't be a reference.
+/// - `#[builder(map = path)]`: `set_<field>` stores
+///   `FieldMapper::map(&path, value)`, where `path` is a function or a
+///   unit struct implementing `expr_builder_pattern::FieldMapper`.
+/// - `#[builder(validate = path)]`: adds `try_set_<field>` returning
+///   `Result<_, ValidationError>`, rejecting the value when
+///   `path(value: Field) -> Result<(), &'static str>` does. The value is
+///   checked after `map`, so the field type must be `Copy`.
+#[proc_macro_derive(Builder, attributes(builder, self_ref))]
+pub fn derive_builder(input: TokenStream) -> TokenStream {
+    match parse::parse_struct(input).and_then(|s| builder::expand(&s)) {
+        Ok(ts) => ts,
+        Err(msg) => parse::compile_error(&msg),
+    }
+}
+
+/// Generates `<Name>Builder` for an enum of unit variants and tuple
+/// variants with one field: `set_<variant>(value)` selects a variant,
+/// `build()` returns it or `BuildError::NoVariantSelected`.
+#[proc_macro_derive(EnumBuilder)]
+pub fn derive_enum_builder(input: TokenStream) -> TokenStream {
+    match parse::parse_enum(input).and_then(|e| enum_builder::expand(&e)) {
+        Ok(ts) => ts,
+        Err(msg) => parse::compile_error(&msg),
+    }
+}
@@ -0,0 +1,405 @@
+//! Minimal parsing of struct definitions straight from `proc_macro` tokens.
+
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+
+pub struct Field {
+    pub name: String,
+    pub ty: String,
+    pub attrs: FieldAttrs,
+}
+
+/// Options from `#[builder(...)]` on a field.
+#[derive(Default)]
+pub struct FieldAttrs {
+    /// `optional`: a missing value falls back to `Default::default()`.
+    pub optional: bool,
+    /// `self_ref(target = "field")`: set by `build()` to point at `field`.
+    pub self_ref: Option<String>,
+    /// `lazy_default = "self_ref"`: when not set, computed by a function of
+    /// the partially initialized value inside `build()`.
+    pub lazy_self_ref: bool,
+    /// `map = path`: the setter stores `FieldMapper::map(&path, value)`.
+    pub map: Option<String>,
+    /// `validate = path`: also generate `try_set_<field>` checking the
+    /// stored value with `path(value) -> Result<(), &'static str>`.
+    pub validate: Option<String>,
+}
+
+/// The generic parameters of a struct, ready to be pasted into an impl.
+#[derive(Default)]
+pub struct Generics {
+    /// Parameters with bounds but without defaults, e.g. `'m, T: Copy`.
+    pub params: Vec<String>,
+    /// Parameter names as used in type position, e.g. `'m, T`.
+    pub args: Vec<String>,
+    /// The contents of the where clause without the `where` keyword.
+    pub where_clause: String,
+}
+
+impl Generics {
+    /// `<'m, T: Copy>` or an empty string.
+    pub fn impl_generics(&self) -> String {
+        angle(&self.params)
+    }
+
+    /// `<'m, T>` or an empty string.
+    pub fn ty_generics(&self) -> String {
+        angle(&self.args)
+    }
+
+    /// `where ...` or an empty string.
+    pub fn where_clause(&self) -> String {
+        if self.where_clause.is_empty() {
+            String::new()
+        } else {
+            format!("where {}", self.where_clause)
+        }
+    }
+}
+
+pub fn angle(items: &[String]) -> String {
+    if items.is_empty() {
+        String::new()
+    } else {
+        format!("<{}>", items.join(", "))
+    }
+}
+
+pub struct Struct {
+    pub vis: String,
+    pub name: String,
+    pub generics: Generics,
+    pub fields: Vec<Field>,
+}
+
+type Tokens = std::iter::Peekable<proc_macro::token_stream::IntoIter>;
+
+pub fn parse_struct(input: TokenStream) -> Result<Struct, String> {
+    let mut tokens = input.into_iter().peekable();
+    skip_attrs(&mut tokens);
+    let vis = parse_vis(&mut tokens);
+    expect_keyword(&mut tokens, "struct")?;
+    let name = expect_ident(&mut tokens)?;
+    let mut generics = parse_generics(&mut tokens)?;
+    generics.where_clause = parse_where(&mut tokens);
+    let body = match tokens.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g,
+        _ => return Err(format!("{} must be a struct with named fields", name)),
+    };
+    let fields = parse_fields(body)?;
+    Ok(Struct {
+        vis,
+        name,
+        generics,
+        fields,
+    })
+}
+
+pub struct Enum {
+    pub vis: String,
+    pub name: String,
+    pub generics: Generics,
+    pub variants: Vec<Variant>,
+}
+
+/// A unit variant or a tuple variant with exactly one field.
+pub struct Variant {
+    pub name: String,
+    pub ty: Option<String>,
+}
+
+pub fn parse_enum(input: TokenStream) -> Result<Enum, String> {
+    let mut tokens = input.into_iter().peekable();
+    skip_attrs(&mut tokens);
+    let vis = parse_vis(&mut tokens);
+    expect_keyword(&mut tokens, "enum")?;
+    let name = expect_ident(&mut tokens)?;
+    let mut generics = parse_generics(&mut tokens)?;
+    generics.where_clause = parse_where(&mut tokens);
+    let body = match tokens.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g,
+        _ => return Err(format!("{} must be an enum", name)),
+    };
+    let mut variants = Vec::new();
+    for chunk in split_top_level_commas(body.stream()) {
+        let mut tokens = chunk.into_iter().peekable();
+        skip_attrs(&mut tokens);
+        let variant = expect_ident(&mut tokens)?;
+        let ty = match tokens.next() {
+            None => None,
+            Some(TokenTree::Group(g))
+                if g.delimiter() == Delimiter::Parenthesis
+                    && split_top_level_commas(g.stream()).len() == 1
+                    && tokens.peek().is_none() =>
+            {
+                Some(g.stream().to_string())
+            }
+            _ => {
+                return Err(format!(
+                    "variant {} must be a unit variant or have exactly one unnamed field",
+                    variant
+                ))
+            }
+        };
+        variants.push(Variant { name: variant, ty });
+    }
+    Ok(Enum {
+        vis,
+        name,
+        generics,
+        variants,
+    })
+}
+
+fn skip_attrs(tokens: &mut Tokens) {
+    while matches!(tokens.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '#') {
+        tokens.next();
+        tokens.next();
+    }
+}
+
+fn parse_vis(tokens: &mut Tokens) -> String {
+    match tokens.peek() {
+        Some(TokenTree::Ident(i)) if i.to_string() == "pub" => {
+            tokens.next();
+            if let Some(TokenTree::Group(g)) = tokens.peek() {
+                if g.delimiter() == Delimiter::Parenthesis {
+                    let restricted = format!("pub{}", g);
+                    tokens.next();
+                    return restricted;
+                }
+            }
+            "pub".to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+fn expect_keyword(tokens: &mut Tokens, keyword: &str) -> Result<(), String> {
+    match tokens.next() {
+        Some(TokenTree::Ident(i)) if i.to_string() == keyword => Ok(()),
+        _ => Err(format!("expected `{}`", keyword)),
+    }
+}
+
+fn expect_ident(tokens: &mut Tokens) -> Result<String, String> {
+    match tokens.next() {
+        Some(TokenTree::Ident(i)) => Ok(i.to_string()),
+        _ => Err("expected an identifier".to_string()),
+    }
+}
+
+fn is_punct(tt: &TokenTree, c: char) -> bool {
+    matches!(tt, TokenTree::Punct(p) if p.as_char() == c)
+}
+
+fn parse_generics(tokens: &mut Tokens) -> Result<Generics, String> {
+    let mut generics = Generics::default();
+    if !matches!(tokens.peek(), Some(tt) if is_punct(tt, '<')) {
+        return Ok(generics);
+    }
+    tokens.next();
+    let mut depth = 1;
+    let mut inner = Vec::new();
+    for tt in tokens.by_ref() {
+        if is_punct(&tt, '<') {
+            depth += 1;
+        } else if is_punct(&tt, '>') {
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        }
+        inner.push(tt);
+    }
+    if depth != 0 {
+        return Err("unterminated generics".to_string());
+    }
+    for param in split_top_level_commas(inner.into_iter().collect()) {
+        let tts: Vec<TokenTree> = param.into_iter().collect();
+        // Drop any `= Default`
+        let mut decl = Vec::new();
+        let mut depth = 0;
+        for tt in &tts {
+            if is_punct(tt, '<') {
+                depth += 1;
+            } else if is_punct(tt, '>') {
+                depth -= 1;
+            } else if depth == 0 && is_punct(tt, '=') {
+                break;
+            }
+            decl.push(tt.clone());
+        }
+        let name = match (decl.first(), decl.get(1)) {
+            (Some(TokenTree::Punct(p)), Some(TokenTree::Ident(i)))
+                if p.as_char() == '\'' && p.spacing() == Spacing::Joint =>
+            {
+                format!("'{}", i)
+            }
+            (Some(TokenTree::Ident(k)), Some(TokenTree::Ident(i))) if k.to_string() == "const" => {
+                i.to_string()
+            }
+            (Some(TokenTree::Ident(i)), _) => i.to_string(),
+            _ => return Err("unsupported generic parameter".to_string()),
+        };
+        generics
+            .params
+            .push(decl.into_iter().collect::<TokenStream>().to_string());
+        generics.args.push(name);
+    }
+    Ok(generics)
+}
+
+fn parse_where(tokens: &mut Tokens) -> String {
+    if !matches!(tokens.peek(), Some(TokenTree::Ident(i)) if i.to_string() == "where") {
+        return String::new();
+    }
+    tokens.next();
+    let mut clause = Vec::new();
+    while let Some(tt) = tokens.peek() {
+        if matches!(tt, TokenTree::Group(g) if g.delimiter() == Delimiter::Brace) {
+            break;
+        }
+        clause.push(tokens.next().unwrap());
+    }
+    let clause = clause.into_iter().collect::<TokenStream>().to_string();
+    clause.trim_end_matches(',').to_string()
+}
+
+fn parse_fields(body: Group) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    for chunk in split_top_level_commas(body.stream()) {
+        let mut tokens = chunk.into_iter().peekable();
+        let attrs = parse_field_attrs(&mut tokens)?;
+        parse_vis(&mut tokens);
+        let name = expect_ident(&mut tokens)?;
+        match tokens.next() {
+            Some(tt) if is_punct(&tt, ':') => {}
+            _ => return Err(format!("expected `:` after field {}", name)),
+        }
+        let ty = tokens.collect::<TokenStream>().to_string();
+        fields.push(Field { name, ty, attrs });
+    }
+    Ok(fields)
+}
+
+/// Collect `#[builder(...)]` options and the `#[self_ref(points_to =
+/// "field")]` shorthand, other attributes are skipped.
+fn parse_field_attrs(tokens: &mut Tokens) -> Result<FieldAttrs, String> {
+    let mut attrs = FieldAttrs::default();
+    while matches!(tokens.peek(), Some(tt) if is_punct(tt, '#')) {
+        tokens.next();
+        let Some(TokenTree::Group(attr)) = tokens.next() else {
+            return Err("expected an attribute".to_string());
+        };
+        let mut inner = attr.stream().into_iter();
+        match (inner.next(), inner.next()) {
+            (Some(TokenTree::Ident(i)), Some(TokenTree::Group(args)))
+                if i.to_string() == "builder" =>
+            {
+                parse_builder_args(args.stream(), &mut attrs)?
+            }
+            (Some(TokenTree::Ident(i)), Some(TokenTree::Group(args)))
+                if i.to_string() == "self_ref" =>
+            {
+                attrs.self_ref = Some(parse_self_ref_target(args.stream(), "points_to")?);
+            }
+            _ => {}
+        }
+    }
+    Ok(attrs)
+}
+
+fn parse_builder_args(stream: TokenStream, attrs: &mut FieldAttrs) -> Result<(), String> {
+    for arg in split_top_level_commas(stream) {
+        let mut arg = arg.into_iter();
+        match (arg.next(), arg.next()) {
+            (Some(TokenTree::Ident(i)), None) if i.to_string() == "optional" => {
+                attrs.optional = true;
+            }
+            (Some(TokenTree::Ident(i)), Some(TokenTree::Group(g)))
+                if i.to_string() == "self_ref" =>
+            {
+                attrs.self_ref = Some(parse_self_ref_target(g.stream(), "target")?);
+            }
+            (Some(TokenTree::Ident(i)), Some(eq))
+                if i.to_string() == "lazy_default" && is_punct(&eq, '=') =>
+            {
+                match arg.next() {
+                    Some(TokenTree::Literal(lit)) if lit.to_string() == "\"self_ref\"" => {
+                        attrs.lazy_self_ref = true;
+                    }
+                    _ => return Err("only `lazy_default = \"self_ref\"` is supported".to_string()),
+                }
+            }
+            (Some(TokenTree::Ident(i)), Some(eq))
+                if (i.to_string() == "map" || i.to_string() == "validate")
+                    && is_punct(&eq, '=') =>
+            {
+                let path: TokenStream = arg.collect();
+                if path.is_empty() {
+                    return Err(format!("expected `{} = path`", i));
+                }
+                if i.to_string() == "map" {
+                    attrs.map = Some(path.to_string());
+                } else {
+                    attrs.validate = Some(path.to_string());
+                }
+            }
+            _ => {
+                return Err("expected `optional`, `self_ref(target = \"field\")`, \
+                    `lazy_default = \"self_ref\"`, `map = path` or `validate = path`"
+                    .to_string())
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The field name from `key = "field"`.
+fn parse_self_ref_target(stream: TokenStream, key: &str) -> Result<String, String> {
+    let tts: Vec<TokenTree> = stream.into_iter().collect();
+    match tts.as_slice() {
+        [TokenTree::Ident(k), eq, TokenTree::Literal(lit)]
+            if k.to_string() == key && is_punct(eq, '=') =>
+        {
+            let lit = lit.to_string();
+            lit.strip_prefix('"')
+                .and_then(|l| l.strip_suffix('"'))
+                .map(str::to_string)
+                .ok_or_else(|| "self_ref target must be a string literal".to_string())
+        }
+        _ => Err(format!("expected `self_ref({} = \"field\")`", key)),
+    }
+}
+
+/// Split on commas that are not nested inside `<...>`; groups are already
+/// atomic token trees so only angle brackets need tracking.
+fn split_top_level_commas(stream: TokenStream) -> Vec<TokenStream> {
+    let mut chunks = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0i32;
+    let mut prev_dash = false;
+    for tt in stream {
+        let dash = is_punct(&tt, '-');
+        if is_punct(&tt, '<') {
+            depth += 1;
+        } else if is_punct(&tt, '>') && !prev_dash {
+            depth -= 1;
+        } else if depth == 0 && is_punct(&tt, ',') {
+            chunks.push(current.drain(..).collect());
+            prev_dash = false;
+            continue;
+        }
+        prev_dash = dash;
+        current.push(tt);
+    }
+    if !current.is_empty() {
+        chunks.push(current.into_iter().collect());
+    }
+    chunks
+}
+
+pub fn compile_error(msg: &str) -> TokenStream {
+    format!("compile_error!({:?});", msg).parse().unwrap()
+}
@@ -0,0 +1,46 @@
+//! Expansion of `#[derive(UninitDrop)]`.
+
+use proc_macro::TokenStream;
+
+use crate::parse::Struct;
+
+const PATH: &str = "::expr_builder_pattern::uninit_box";
+
+pub fn expand(s: &Struct) -> TokenStream {
+    let impl_generics = s.generics.impl_generics();
+    let ty = format!("{}{}", s.name, s.generics.ty_generics());
+    let where_clause = s.generics.where_clause();
+
+    let infos: Vec<String> = s
+        .fields
+        .iter()
+        .map(|f| {
+            format!(
+                "{PATH}::FieldInfo {{ \
+                    offset: ::core::mem::offset_of!(Self, {name}), \
+                    drop: {PATH}::drop_field::<{fty}>, \
+                }}",
+                name = f.name,
+                fty = f.ty,
+            )
+        })
+        .collect();
+
+    let mut out = format!(
+        "unsafe impl {impl_generics} {PATH}::UninitDrop for {ty} {where_clause} {{ \
+            const FIELDS: &'static [{PATH}::FieldInfo] = &[{infos}]; \
+        }}",
+        infos = infos.join(", "),
+    );
+    for (idx, f) in s.fields.iter().enumerate() {
+        out.push_str(&format!(
+            "unsafe impl {impl_generics} {PATH}::FieldAt<{idx}> for {ty} {where_clause} {{ \
+                type Type = {fty}; \
+                const OFFSET: usize = ::core::mem::offset_of!(Self, {name}); \
+            }}",
+            fty = f.ty,
+            name = f.name,
+        ));
+    }
+    out.parse().unwrap()
+}
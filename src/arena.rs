@@ -0,0 +1,334 @@
+//! Many linked `Astruct`s, or values of any type, packed into a few large
+//! allocations.
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::mem::{self, MaybeUninit};
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+
+use crate::in_place::InPlaceInit;
+use crate::selfref::Astruct;
+
+const FIRST_CHUNK: usize = 16;
+const FIRST_BYTE_CHUNK: usize = 1024;
+const BYTE_CHUNK_ALIGN: usize = 16;
+
+/// One allocation of `cap` slots of which the first `len` are initialized.
+struct Chunk<T> {
+    slots: NonNull<MaybeUninit<Astruct<T>>>,
+    cap: usize,
+    len: usize,
+}
+
+impl<T> Chunk<T> {
+    fn new(cap: usize) -> Self {
+        let slots = Box::<[Astruct<T>]>::new_uninit_slice(cap);
+        // Only raw pointers to the slots are kept so handing out `&mut`
+        // to one slot never aliases a reference covering the whole chunk.
+        let slots = NonNull::new(Box::into_raw(slots).cast()).unwrap();
+        Chunk { slots, cap, len: 0 }
+    }
+}
+
+/// Bump allocator for linked [`Astruct`]s.
+///
+/// Slots live in chunks that are never reallocated or freed before the
+/// arena is dropped, so each value keeps its address and its `op_a_u32`
+/// stays valid for the lifetime of the arena. A full chunk is followed by
+/// one twice its size. Dropping the arena drops every value it handed out.
+pub struct AstructArena<T = u32> {
+    chunks: RefCell<Vec<Chunk<T>>>,
+}
+
+impl<T> AstructArena<T> {
+    pub fn new() -> Self {
+        AstructArena {
+            chunks: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Move a new `Astruct` into the arena and link it to its slot.
+    // Every call hands out a distinct slot, as typed arenas do
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_astruct(&self, a_u8: u8, a_u32: T) -> Pin<&mut Astruct<T>> {
+        let mut chunks = self.chunks.borrow_mut();
+        let full = chunks.last().is_none_or(|c| c.len == c.cap);
+        if full {
+            let cap = chunks.last().map_or(FIRST_CHUNK, |c| c.cap * 2);
+            chunks.push(Chunk::new(cap));
+        }
+        let chunk = chunks.last_mut().unwrap();
+        // Safety: `len < cap` so the slot is in bounds and unused, it is
+        // written before the reference is created and is never moved or
+        // reused until the arena drops it.
+        unsafe {
+            let slot = chunk.slots.as_ptr().add(chunk.len).cast::<Astruct<T>>();
+            slot.write(Astruct::new(a_u8, a_u32));
+            chunk.len += 1;
+            let mut a = Pin::new_unchecked(&mut *slot);
+            a.as_mut().link();
+            a
+        }
+    }
+
+    /// Number of values allocated so far.
+    pub fn len(&self) -> usize {
+        self.chunks.borrow().iter().map(|c| c.len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for AstructArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for AstructArena<T> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.get_mut().drain(..) {
+            // Safety: the first `len` slots are initialized and no longer
+            // borrowed, the chunk came from `Box::into_raw` with `cap` slots.
+            unsafe {
+                let slots = core::ptr::slice_from_raw_parts_mut(chunk.slots.as_ptr(), chunk.cap);
+                for i in 0..chunk.len {
+                    (*slots)[i].assume_init_drop();
+                }
+                drop(Box::from_raw(slots));
+            }
+        }
+    }
+}
+
+/// Bump allocator for values of any `'static` type, each built in place.
+///
+/// Like [`AstructArena`] values are carved out of chunks that are never
+/// reallocated, a full chunk is followed by a new one twice its size, so
+/// nothing moves once allocated and self references stay valid. Dropping
+/// the arena drops every value in reverse allocation order, then frees the
+/// chunks.
+pub struct Arena {
+    inner: RefCell<ArenaInner>,
+}
+
+struct ArenaInner {
+    chunks: Vec<(NonNull<u8>, Layout)>,
+    // Unused bytes of the last chunk
+    next: usize,
+    end: usize,
+    drops: Vec<(*mut u8, DropFn)>,
+}
+
+type DropFn = unsafe fn(*mut u8);
+
+unsafe fn drop_raw<T>(p: *mut u8) {
+    ptr::drop_in_place(p.cast::<T>());
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Arena {
+            inner: RefCell::new(ArenaInner {
+                chunks: Vec::new(),
+                next: 0,
+                end: 0,
+                drops: Vec::new(),
+            }),
+        }
+    }
+
+    /// A linked `Astruct` in the arena, initialized in its slot with
+    /// [`InPlaceInit`].
+    // Every call hands out a distinct slot, as typed arenas do
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_linked_astruct<T: 'static>(&self, a_u8: u8, a_u32: T) -> Pin<&mut Astruct<T>> {
+        // Safety: init_in_place writes every field
+        unsafe { self.alloc_with(|p| Astruct::init_in_place(p, (a_u8, a_u32))) }
+    }
+
+    /// Reserve a slot for a `T` and let `init` write it at its final
+    /// address, e.g. to point a field at another one.
+    ///
+    /// The value is pinned as the slot is never moved or reused before
+    /// the arena drops it. If `init` panics the slot is left unused.
+    ///
+    /// # Safety
+    ///
+    /// `init` must leave a valid `T` behind the pointer.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn alloc_with<T: 'static>(&self, init: impl FnOnce(*mut T)) -> Pin<&mut T> {
+        let p = self.reserve(Layout::new::<T>()).cast::<T>();
+        // The arena isn't borrowed while init runs, it may allocate too
+        init(p);
+        if mem::needs_drop::<T>() {
+            self.inner
+                .borrow_mut()
+                .drops
+                .push((p.cast(), drop_raw::<T>));
+        }
+        Pin::new_unchecked(&mut *p)
+    }
+
+    /// Number of chunks allocated so far.
+    pub fn chunk_count(&self) -> usize {
+        self.inner.borrow().chunks.len()
+    }
+
+    /// Bump `layout.size()` bytes off the last chunk, starting a new one
+    /// when they don't fit.
+    fn reserve(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == 0 {
+            return ptr::without_provenance_mut(layout.align());
+        }
+        let mut inner = self.inner.borrow_mut();
+        let start = inner.next.next_multiple_of(layout.align());
+        if inner.chunks.is_empty() || start + layout.size() > inner.end {
+            let next = inner
+                .chunks
+                .last()
+                .map_or(FIRST_BYTE_CHUNK, |c| c.1.size() * 2);
+            // Room for the value however the chunk start is aligned
+            let size = next.max(layout.size() + layout.align());
+            let chunk = Layout::from_size_align(size, BYTE_CHUNK_ALIGN).unwrap();
+            // Safety: size is not zero
+            let base =
+                NonNull::new(unsafe { alloc(chunk) }).unwrap_or_else(|| handle_alloc_error(chunk));
+            inner.chunks.push((base, chunk));
+            inner.next = base.as_ptr() as usize;
+            inner.end = inner.next + size;
+        }
+        let (base, _) = *inner.chunks.last().unwrap();
+        let start = inner.next.next_multiple_of(layout.align());
+        inner.next = start + layout.size();
+        // Derived from the chunk pointer to keep its provenance
+        base.as_ptr().wrapping_add(start - base.as_ptr() as usize)
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let inner = self.inner.get_mut();
+        for (p, drop_fn) in inner.drops.drain(..).rev() {
+            // Safety: p holds a value written by alloc_with that is no
+            // longer borrowed, dropped once
+            unsafe { drop_fn(p) };
+        }
+        for (base, layout) in inner.chunks.drain(..) {
+            // Safety: allocated with this layout in reserve
+            unsafe { dealloc(base.as_ptr(), layout) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    #[test]
+    fn thousand_entries_link_to_their_slots() {
+        let arena = AstructArena::new();
+        let all: Vec<_> = (0..1000u32)
+            .map(|i| arena.alloc_astruct(i as u8, i))
+            .collect();
+        assert_eq!(arena.len(), 1000);
+        for (i, a) in all.iter().enumerate() {
+            assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+            assert_eq!(a.referenced_u32(), Some(i as u32));
+            assert_eq!(a.a_u8, i as u8);
+        }
+    }
+
+    #[test]
+    fn entries_can_be_updated_in_place() {
+        let arena = AstructArena::new();
+        let mut a = arena.alloc_astruct(1, 2u32);
+        let b = arena.alloc_astruct(3, 4);
+        a.as_mut().set_a_u32(5);
+        assert_eq!(a.referenced_u32(), Some(5));
+        assert_eq!(b.referenced_u32(), Some(4));
+    }
+
+    #[test]
+    fn dropping_the_arena_drops_the_entries() {
+        let payload = Rc::new(());
+        let arena = AstructArena::new();
+        for _ in 0..40 {
+            arena.alloc_astruct(0, payload.clone());
+        }
+        assert_eq!(Rc::strong_count(&payload), 41);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&payload), 1);
+    }
+
+    // Counts its drops in the shared cell
+    struct DropCount(Rc<Cell<usize>>);
+
+    impl Drop for DropCount {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn values_keep_their_address_across_chunks() {
+        let arena = Arena::new();
+        let all: Vec<_> = (0..1000u32)
+            .map(|i| &*arena.alloc_linked_astruct(i as u8, i) as *const Astruct)
+            .collect();
+        assert!(arena.chunk_count() > 3);
+        for (i, &a) in all.iter().enumerate() {
+            // Safety: the arena is alive and never moves its values
+            let a = unsafe { &*a };
+            assert_eq!(a.validate(), Ok(()));
+            assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+            assert_eq!((a.a_u8, a.referenced_u32()), (i as u8, Some(i as u32)));
+        }
+    }
+
+    #[test]
+    fn every_value_is_dropped_once() {
+        let drops = Rc::new(Cell::new(0));
+        let arena = Arena::default();
+        for i in 0..300u32 {
+            arena.alloc_linked_astruct(0, DropCount(drops.clone()));
+            arena.alloc_linked_astruct(1, i);
+        }
+        assert!(arena.chunk_count() > 1);
+        assert_eq!(drops.get(), 0);
+        drop(arena);
+        assert_eq!(drops.get(), 300);
+        assert_eq!(Rc::strong_count(&drops), 1);
+    }
+
+    #[test]
+    fn alloc_with_respects_alignment() {
+        #[repr(align(64))]
+        struct Wide(u8);
+
+        let arena = Arena::new();
+        arena.alloc_linked_astruct(1, 1u8);
+        for i in 0..40 {
+            // Safety: writes the whole value
+            let w = unsafe { arena.alloc_with(|p: *mut Wide| p.write(Wide(i))) };
+            assert_eq!(&*w as *const Wide as usize % 64, 0);
+            assert_eq!(w.0, i);
+        }
+        let unit = unsafe { arena.alloc_with(|p: *mut ()| p.write(())) };
+        assert_eq!(*unit, ());
+    }
+}
@@ -0,0 +1,142 @@
+//! A builder whose field values can come from futures.
+
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+
+use crate::errors::BuildError;
+use crate::selfref::Astruct;
+
+/// Collects the fields of an [`Astruct`] where `a_u32` may first have to
+/// be fetched, e.g. from a database or remote config.
+///
+/// Each `_async` setter awaits its future before returning the builder,
+/// so fields are resolved in the order they are set and `build()` only
+/// links the result. The builder and its futures are `Send` when `T` and
+/// the awaited futures are, so it can be driven on a multi threaded
+/// executor.
+#[derive(Clone, Debug)]
+pub struct AsyncAstructBuilder<T = u32> {
+    a_u8: Option<u8>,
+    a_u32: Option<T>,
+}
+
+// Not derived so `T` needn't be `Default`
+impl<T> Default for AsyncAstructBuilder<T> {
+    fn default() -> Self {
+        AsyncAstructBuilder {
+            a_u8: None,
+            a_u32: None,
+        }
+    }
+}
+
+impl<T> AsyncAstructBuilder<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_a_u8(mut self, a_u8: u8) -> Self {
+        self.a_u8 = Some(a_u8);
+        self
+    }
+
+    pub fn set_a_u32(mut self, a_u32: T) -> Self {
+        self.a_u32 = Some(a_u32);
+        self
+    }
+
+    /// Await `fut` and store its output as `a_u32`.
+    pub async fn set_a_u32_async<F: Future<Output = T>>(self, fut: F) -> Self {
+        let a_u32 = fut.await;
+        self.set_a_u32(a_u32)
+    }
+
+    /// Build a pinned `Astruct` linked to its own `a_u32`, like
+    /// `AstructBuilder::build`. `a_u8` defaults to 0.
+    pub async fn build(self) -> Result<Pin<Box<Astruct<T>>>, BuildError> {
+        let a_u32 = self
+            .a_u32
+            .ok_or(BuildError::MissingField { name: "a_u32" })?;
+        Ok(Astruct::boxed_linked(self.a_u8.unwrap_or_default(), a_u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::ready;
+    use core::task::{Context, Poll, Waker};
+
+    // Polls until ready, the futures here never wait on anything external
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = core::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(v) = fut.as_mut().poll(&mut cx) {
+                return v;
+            }
+        }
+    }
+
+    // Pending once before completing, like a real I/O future
+    struct YieldOnce(Option<u32>, bool);
+
+    impl Future for YieldOnce {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<u32> {
+            if !self.1 {
+                self.1 = true;
+                return Poll::Pending;
+            }
+            Poll::Ready(self.0.take().unwrap())
+        }
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn builds_from_awaited_value() {
+        let a = block_on(async {
+            AsyncAstructBuilder::new()
+                .set_a_u8(1)
+                .set_a_u32_async(YieldOnce(Some(123), false))
+                .await
+                .build()
+                .await
+        })
+        .unwrap();
+        assert_eq!((a.a_u8, a.referenced_u32()), (1, Some(123)));
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn later_setter_wins() {
+        let a = block_on(async {
+            AsyncAstructBuilder::new()
+                .set_a_u32_async(ready(1u32))
+                .await
+                .set_a_u32(2)
+                .build()
+                .await
+        })
+        .unwrap();
+        assert_eq!((a.a_u8, a.a_u32), (0, 2));
+    }
+
+    #[test]
+    fn missing_a_u32_is_reported() {
+        let err = block_on(AsyncAstructBuilder::<u32>::new().set_a_u8(1).build()).unwrap_err();
+        assert_eq!(err, BuildError::MissingField { name: "a_u32" });
+    }
+
+    #[test]
+    fn futures_are_send() {
+        let b = AsyncAstructBuilder::new();
+        assert_send(&b);
+        let fut = b.set_a_u32_async(ready(1u32));
+        assert_send(&fut);
+        assert_send(&AsyncAstructBuilder::<u32>::new().build());
+    }
+}
@@ -0,0 +1,783 @@
+//! Builders producing linked `Astruct`s.
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+#[cfg(feature = "allocator_api")]
+use crate::in_place::InPlaceInit;
+use crate::selfref::{Astruct, Astruct2};
+use crate::validator::{ValidationError, Validator};
+
+/// Typestate marker: `a_u32` has not been provided yet.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsU32;
+
+/// Typestate marker: every required field is known, `link()` is available.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unlinked;
+
+/// Typestate marker: `link()` was called, `build()` is available.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Linked;
+
+/// Typestate marker: `point_at_u8()` was called, `build()` is available
+/// and links `a_u8` instead of `a_u32`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkedU8;
+
+/// Collects the fields of an [`Astruct`] and builds it with `op_a_u32`
+/// already pointing at the boxed `a_u32`.
+///
+/// The state parameter `S` only exists at compile time, so it costs nothing
+/// at runtime. [`set_a_u32`](AstructBuilder::set_a_u32) moves to
+/// `Unlinked`, [`link`](AstructBuilder::link) from there to `Linked` and
+/// `build()` is only implemented for `AstructBuilder<Linked>`:
+///
+/// ```
+/// use expr_builder_pattern::AstructBuilder;
+///
+/// let a = AstructBuilder::new().set_a_u8(1).set_a_u32(2u32).link().build();
+/// assert_eq!(a.referenced_u32(), Some(2));
+/// ```
+///
+/// ```compile_fail
+/// use expr_builder_pattern::AstructBuilder;
+///
+/// // a_u32 was never set so there is no link() or build()
+/// let a = AstructBuilder::new().set_a_u8(1).link().build();
+/// ```
+///
+/// ```compile_fail
+/// use expr_builder_pattern::AstructBuilder;
+///
+/// // link() was never called so there is no build()
+/// let a = AstructBuilder::new().set_a_u8(1).set_a_u32(2u32).build();
+/// ```
+///
+/// `P` is the builder this one was started from when building an
+/// `Astruct` as part of a larger value, e.g. [`AstructKindBuilder::large`].
+/// Such a sub-builder finishes into its parent instead of having `build()`.
+///
+/// [`AstructKindBuilder::large`]: crate::enum_builder::AstructKindBuilder::large
+#[derive(Clone, Debug)]
+pub struct AstructBuilder<S = NeedsU32, T = u32, P = ()> {
+    pub(crate) a_u8: Option<u8>,
+    pub(crate) a_u32: Option<T>,
+    // Set by include_a_u8_if(false): a_u8 is built as 0 and left unlinked
+    pub(crate) skip_a_u8: bool,
+    pub(crate) parent: P,
+    pub(crate) _state: PhantomData<S>,
+}
+
+// Not derived so `T` needn't be `Default`
+impl<S, T, P: Default> Default for AstructBuilder<S, T, P> {
+    fn default() -> Self {
+        Self::with_parent(P::default())
+    }
+}
+
+impl<T> AstructBuilder<NeedsU32, T> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S, T, P> AstructBuilder<S, T, P> {
+    /// An empty sub-builder of `parent`.
+    #[inline]
+    pub(crate) fn with_parent(parent: P) -> Self {
+        AstructBuilder {
+            a_u8: None,
+            a_u32: None,
+            skip_a_u8: false,
+            parent,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<T, P> AstructBuilder<NeedsU32, T, P> {
+    /// Clear every field so the builder can be reused.
+    ///
+    /// Only available before `set_a_u32`, the later states promise that
+    /// `a_u32` is set. To reuse a builder across loop iterations, build
+    /// with [`build_and_reset`](AstructBuilder::build_and_reset), which
+    /// hands it back in this state.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.a_u8 = None;
+        self.a_u32 = None;
+        self.skip_a_u8 = false;
+    }
+}
+
+/// The field values of an [`AstructBuilder`] in state `S`, see
+/// [`snapshot`](AstructBuilder::snapshot). `Copy` whenever `T` is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AstructBuilderSnapshot<S = NeedsU32, T = u32> {
+    a_u8: Option<u8>,
+    a_u32: Option<T>,
+    skip_a_u8: bool,
+    _state: PhantomData<S>,
+}
+
+impl<S, T, P> AstructBuilder<S, T, P> {
+    /// Copy the current field values, to [`restore`](AstructBuilder::restore)
+    /// them after trying out different completions.
+    pub fn snapshot(&self) -> AstructBuilderSnapshot<S, T>
+    where
+        T: Copy,
+    {
+        AstructBuilderSnapshot {
+            a_u8: self.a_u8,
+            a_u32: self.a_u32,
+            skip_a_u8: self.skip_a_u8,
+            _state: PhantomData,
+        }
+    }
+
+    /// Roll back to `snap`, taken from a builder in the same state.
+    pub fn restore(&mut self, snap: AstructBuilderSnapshot<S, T>) {
+        self.a_u8 = snap.a_u8;
+        self.a_u32 = snap.a_u32;
+        self.skip_a_u8 = snap.skip_a_u8;
+    }
+
+    #[inline]
+    pub fn set_a_u8(mut self, a_u8: u8) -> Self {
+        self.a_u8 = Some(a_u8);
+        self
+    }
+
+    /// Leave `a_u8` out of the built `Astruct` unless `cond` holds.
+    ///
+    /// When skipped `a_u8` is built as 0 whatever was set and `op_a_u32`
+    /// as `None`, the link depends on `a_u8` being meaningful. Calling it
+    /// again with `true` includes `a_u8` again.
+    #[inline]
+    pub fn include_a_u8_if(mut self, cond: bool) -> Self {
+        self.skip_a_u8 = !cond;
+        self
+    }
+
+    #[inline]
+    pub fn set_a_u32(self, a_u32: T) -> AstructBuilder<Unlinked, T, P> {
+        AstructBuilder {
+            a_u8: self.a_u8,
+            a_u32: Some(a_u32),
+            skip_a_u8: self.skip_a_u8,
+            parent: self.parent,
+            _state: PhantomData,
+        }
+    }
+
+    /// [`set_a_u32`](AstructBuilder::set_a_u32) storing `mapper(a_u32)`,
+    /// e.g. to normalize it first.
+    #[inline]
+    pub fn set_a_u32_mapped(
+        self,
+        a_u32: T,
+        mapper: impl FnOnce(T) -> T,
+    ) -> AstructBuilder<Unlinked, T, P> {
+        self.set_a_u32(mapper(a_u32))
+    }
+
+    /// [`set_a_u8`](AstructBuilder::set_a_u8) storing `a_u8` only if
+    /// `check` accepts it, like a derived `#[builder(validate = path)]`
+    /// setter. `Astruct` reserves no values itself, `check` is where a
+    /// protocol rejects the ones it does:
+    ///
+    /// ```
+    /// use expr_builder_pattern::AstructBuilder;
+    ///
+    /// // This protocol reserves 0 for "no version"
+    /// fn version(v: u8) -> Result<(), &'static str> {
+    ///     if v == 0 {
+    ///         Err("0 is reserved for no version")
+    ///     } else {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let err = AstructBuilder::<_, u32>::new().try_set_a_u8(0, version).unwrap_err();
+    /// assert_eq!(err.to_string(), "a_u8: 0 is reserved for no version");
+    /// ```
+    pub fn try_set_a_u8(
+        self,
+        a_u8: u8,
+        check: impl FnOnce(u8) -> Result<(), &'static str>,
+    ) -> Result<Self, ValidationError> {
+        match check(a_u8) {
+            Ok(()) => Ok(self.set_a_u8(a_u8)),
+            Err(msg) => Err(ValidationError {
+                field_name: "a_u8",
+                message: msg.into(),
+            }),
+        }
+    }
+
+    /// [`set_a_u32`](AstructBuilder::set_a_u32) storing `a_u32` only if
+    /// `check` accepts it, see [`try_set_a_u8`](AstructBuilder::try_set_a_u8).
+    pub fn try_set_a_u32(
+        self,
+        a_u32: T,
+        check: impl FnOnce(&T) -> Result<(), &'static str>,
+    ) -> Result<AstructBuilder<Unlinked, T, P>, ValidationError> {
+        match check(&a_u32) {
+            Ok(()) => Ok(self.set_a_u32(a_u32)),
+            Err(msg) => Err(ValidationError {
+                field_name: "a_u32",
+                message: msg.into(),
+            }),
+        }
+    }
+}
+
+impl<T> AstructBuilder<Unlinked, T> {
+    /// Start from the values of an existing `Astruct`, consuming it.
+    ///
+    /// Setters then override single fields. The old `op_a_u32` is not
+    /// carried over, `build()` links into the new allocation.
+    // Boxed as that is how built values are handed out
+    #[allow(clippy::boxed_local)]
+    pub fn from_existing(a: Box<Astruct<T>>) -> Self {
+        let Astruct { a_u8, a_u32, .. } = *a;
+        AstructBuilder {
+            a_u8: Some(a_u8),
+            a_u32: Some(a_u32),
+            skip_a_u8: false,
+            parent: (),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<T, P> AstructBuilder<Unlinked, T, P> {
+    /// Commit to building with `op_a_u32` pointing at `a_u32`.
+    ///
+    /// The pointer itself can only be written once the final allocation
+    /// exists, so this just unlocks `build()`, which establishes it.
+    #[inline]
+    pub fn link(self) -> AstructBuilder<Linked, T, P> {
+        AstructBuilder {
+            a_u8: self.a_u8,
+            a_u32: self.a_u32,
+            skip_a_u8: self.skip_a_u8,
+            parent: self.parent,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<P> AstructBuilder<Unlinked, u32, P> {
+    /// Commit to building an [`Astruct2`] whose `op_a_u8` points at its
+    /// `a_u8`, with `op_a_u32` left `None`.
+    ///
+    /// ```
+    /// use expr_builder_pattern::AstructBuilder;
+    ///
+    /// let a = AstructBuilder::new().set_a_u8(1).set_a_u32(2).point_at_u8().build();
+    /// assert_eq!((a.linked_a_u8(), a.linked_a_u32()), (Some(1), None));
+    /// ```
+    #[inline]
+    pub fn point_at_u8(self) -> AstructBuilder<LinkedU8, u32, P> {
+        AstructBuilder {
+            a_u8: self.a_u8,
+            a_u32: self.a_u32,
+            skip_a_u8: self.skip_a_u8,
+            parent: self.parent,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<S, T, P> AstructBuilder<S, T, P> {
+    /// `a_u8` defaults to 0. Only called in the `Linked` and `LinkedU8`
+    /// states, which always have `a_u32`.
+    fn into_parts(self) -> (Parts<T>, P) {
+        let a_u8 = match self.skip_a_u8 {
+            true => 0,
+            false => self.a_u8.unwrap_or_default(),
+        };
+        match self.a_u32 {
+            Some(a_u32) => {
+                let link = !self.skip_a_u8;
+                (Parts { a_u8, a_u32, link }, self.parent)
+            }
+            None => unreachable!("AstructBuilder<Linked> always has a_u32"),
+        }
+    }
+}
+
+impl<T, P> AstructBuilder<Linked, T, P> {
+    /// Build like [`build`](AstructBuilder::build) and hand back the
+    /// parent to store the result in.
+    pub(crate) fn build_for_parent(self) -> (P, Pin<Box<Astruct<T>>>) {
+        let (parts, parent) = self.into_parts();
+        (parent, Box::into_pin(parts.boxed()))
+    }
+}
+
+/// What a `Linked` builder builds.
+struct Parts<T> {
+    a_u8: u8,
+    a_u32: T,
+    link: bool,
+}
+
+impl<T> Parts<T> {
+    fn boxed(self) -> Box<Astruct<T>> {
+        match self.link {
+            true => Astruct::linked_box(self.a_u8, self.a_u32),
+            false => Astruct::boxed_unlinked(self.a_u8, self.a_u32),
+        }
+    }
+}
+
+impl<T> AstructBuilder<Linked, T> {
+    fn values(self) -> Parts<T> {
+        let (parts, ()) = self.into_parts();
+        parts
+    }
+
+    /// Build a pinned `Astruct` whose link is established in place.
+    ///
+    /// `op_a_u32` is written inside the uninitialized allocation from the
+    /// now final address of `a_u32`, so the returned value is never
+    /// unlinked, except when `a_u8` was left out with
+    /// [`include_a_u8_if`](AstructBuilder::include_a_u8_if). The box is
+    /// pinned because moving the value out of it would leave `op_a_u32`
+    /// pointing at the old location.
+    #[inline]
+    pub fn build(self) -> Pin<Box<Astruct<T>>> {
+        Box::into_pin(self.values().boxed())
+    }
+
+    /// [`build`](AstructBuilder::build) and hand the builder back with
+    /// every field cleared, ready for the next value:
+    ///
+    /// ```
+    /// use expr_builder_pattern::AstructBuilder;
+    ///
+    /// let mut b = AstructBuilder::new();
+    /// for i in 0..3u32 {
+    ///     let (a, reset) = b.set_a_u8(1).set_a_u32(i).link().build_and_reset();
+    ///     assert_eq!(a.referenced_u32(), Some(i));
+    ///     b = reset;
+    /// }
+    /// ```
+    #[inline]
+    pub fn build_and_reset(self) -> (Pin<Box<Astruct<T>>>, AstructBuilder<NeedsU32, T>) {
+        let (parts, parent) = self.into_parts();
+        (
+            Box::into_pin(parts.boxed()),
+            AstructBuilder::with_parent(parent),
+        )
+    }
+
+    /// Build an unpinned boxed `Astruct` with the link established.
+    ///
+    /// Moving the `Box` itself is fine since the heap allocation stays put,
+    /// only moving the `Astruct` out of the box would break the link.
+    #[inline]
+    pub fn build_linked(self) -> Box<Astruct<T>> {
+        self.values().boxed()
+    }
+
+    /// [`build`](AstructBuilder::build) into memory from `alloc`, e.g. a
+    /// bump or pool allocator. Needs nightly and the `allocator_api`
+    /// feature.
+    #[cfg(feature = "allocator_api")]
+    pub fn build_in<A>(self, alloc: A) -> Pin<Box<Astruct<T>, A>>
+    where
+        A: core::alloc::Allocator + 'static,
+    {
+        let Parts { a_u8, a_u32, link } = self.values();
+        let mut b = Box::<Astruct<T>, A>::new_uninit_in(alloc);
+        unsafe {
+            // Safety: the allocation is final, init_in_place writes every
+            // field
+            Astruct::init_in_place(b.as_mut_ptr(), (a_u8, a_u32));
+            if !link {
+                (*b.as_mut_ptr()).op_a_u32 = None;
+            }
+            Box::into_pin(b.assume_init())
+        }
+    }
+
+    /// [`build_linked`](AstructBuilder::build_linked) followed by `v`, the
+    /// box is only returned if it passes.
+    pub fn build_validated(
+        self,
+        v: impl Validator<Astruct<T>>,
+    ) -> Result<Box<Astruct<T>>, ValidationError> {
+        let built = self.build_linked();
+        v.validate(&built)?;
+        Ok(built)
+    }
+}
+
+impl AstructBuilder<LinkedU8> {
+    /// Build a pinned `Astruct2` with `op_a_u8` pointing at its own
+    /// `a_u8`. Left unlinked like `build()` when `a_u8` is left out with
+    /// [`include_a_u8_if`](AstructBuilder::include_a_u8_if).
+    pub fn build(self) -> Pin<Box<Astruct2>> {
+        let (Parts { a_u8, a_u32, link }, ()) = self.into_parts();
+        let mut a = Astruct2::boxed_linked_u8(a_u8, a_u32);
+        if !link {
+            // Safety: clearing a link moves nothing
+            unsafe { a.as_mut().get_unchecked_mut().op_a_u8 = None };
+        }
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::SelfRefValidator;
+    use core::ptr;
+
+    #[test]
+    fn build_links_op_a_u32() {
+        let a = AstructBuilder::new()
+            .set_a_u8(1)
+            .set_a_u32(123)
+            .link()
+            .build();
+        assert_eq!(a.a_u8, 1);
+        assert_eq!(a.a_u32, 123);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn build_never_leaves_op_a_u32_unset() {
+        let built = AstructBuilder::new()
+            .set_a_u8(4)
+            .set_a_u32(456u32)
+            .link()
+            .build();
+        assert!(built.referenced_u32().is_some());
+        assert_eq!(
+            &built.a_u32 as *const u32,
+            built.link_ptr().unwrap().as_ptr() as *const u32
+        );
+    }
+
+    fn pass_through(a: Box<Astruct>) -> Box<Astruct> {
+        a
+    }
+
+    #[test]
+    fn build_linked_survives_moving_the_box() {
+        let boxed = AstructBuilder::new()
+            .set_a_u8(1)
+            .set_a_u32(2)
+            .link()
+            .build_linked();
+        assert!(ptr::eq(&boxed.a_u32, boxed.link_ptr().unwrap().as_ptr()));
+
+        let boxed = pass_through(boxed);
+        assert!(ptr::eq(&boxed.a_u32, boxed.link_ptr().unwrap().as_ptr()));
+
+        let mut v = Vec::new();
+        v.push(boxed);
+        for i in 0..64 {
+            v.push(AstructBuilder::new().set_a_u32(i).link().build_linked());
+        }
+        for boxed in &v {
+            assert!(ptr::eq(&boxed.a_u32, boxed.link_ptr().unwrap().as_ptr()));
+        }
+
+        let boxed = v.swap_remove(0);
+        assert_eq!(boxed.referenced_u32().unwrap(), 2);
+        assert!(ptr::eq(&boxed.a_u32, boxed.link_ptr().unwrap().as_ptr()));
+    }
+
+    #[test]
+    fn a_u8_defaults_to_zero() {
+        let a = AstructBuilder::new().set_a_u32(9).link().build();
+        assert_eq!(a.a_u8, 0);
+        assert_eq!(a.a_u32, 9);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn setters_work_in_any_order() {
+        let a = AstructBuilder::new()
+            .set_a_u32(9)
+            .set_a_u8(2)
+            .link()
+            .build();
+        assert_eq!(a.a_u8, 2);
+        assert_eq!(a.a_u32, 9);
+    }
+
+    #[test]
+    fn build_validated_passes_self_ref_validator() {
+        let a = AstructBuilder::new()
+            .set_a_u8(1)
+            .set_a_u32(2)
+            .link()
+            .build_validated(SelfRefValidator)
+            .unwrap();
+        assert_eq!(a.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn build_validated_reports_failure() {
+        let err = AstructBuilder::new()
+            .set_a_u32(2)
+            .link()
+            .build_validated(|_: &Astruct| {
+                Err(ValidationError {
+                    field_name: "a_u32",
+                    message: "rejected".into(),
+                })
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "a_u32: rejected");
+    }
+
+    #[test]
+    fn set_a_u32_mapped_stores_mapped_value() {
+        let a = AstructBuilder::new()
+            .set_a_u32_mapped(7u32, |v| v & !3)
+            .link()
+            .build();
+        assert_eq!(a.a_u32, 4);
+        assert_eq!(a.referenced_u32(), Some(4));
+    }
+
+    #[test]
+    fn try_setters_store_what_check_accepts() {
+        let nonzero_u8 = |v: u8| if v == 0 { Err("zero") } else { Ok(()) };
+        let nonzero_u32 = |v: &u32| if *v == 0 { Err("zero") } else { Ok(()) };
+        let a = AstructBuilder::new()
+            .try_set_a_u8(1, nonzero_u8)
+            .and_then(|b| b.try_set_a_u32(2, nonzero_u32))
+            .unwrap()
+            .link()
+            .build();
+        assert_eq!((a.a_u8, a.referenced_u32()), (1, Some(2)));
+
+        let err = AstructBuilder::<NeedsU32>::new()
+            .try_set_a_u8(0, nonzero_u8)
+            .unwrap_err();
+        assert_eq!(err.field_name, "a_u8");
+        let err = AstructBuilder::new()
+            .try_set_a_u32(0u32, nonzero_u32)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "a_u32: zero");
+
+        // Without a rule 0 is a valid value
+        let a = AstructBuilder::new()
+            .try_set_a_u32(0u32, |_| Ok(()))
+            .unwrap()
+            .link()
+            .build();
+        assert_eq!(a.referenced_u32(), Some(0));
+    }
+
+    #[test]
+    fn reset_clears_fields() {
+        let mut b = AstructBuilder::new().set_a_u8(7);
+        b.reset();
+        assert_eq!(b.a_u8, None);
+        let a = b.set_a_u32(1u32).link().build();
+        assert_eq!(a.a_u8, 0);
+    }
+
+    #[test]
+    fn build_and_reset_hands_back_an_empty_builder() {
+        let (a, b) = AstructBuilder::new()
+            .set_a_u8(7)
+            .include_a_u8_if(false)
+            .set_a_u32(1u32)
+            .link()
+            .build_and_reset();
+        assert_eq!((a.a_u8, a.a_u32), (0, 1));
+        assert_eq!((b.a_u8, b.a_u32, b.skip_a_u8), (None, None, false));
+        let (a, _) = b.set_a_u32(2).link().build_and_reset();
+        assert_eq!((a.a_u8, a.referenced_u32()), (0, Some(2)));
+    }
+
+    #[test]
+    fn include_a_u8_if_false_zeroes_and_unlinks() {
+        for cond in [true, false] {
+            let b = AstructBuilder::new()
+                .set_a_u8(7)
+                .include_a_u8_if(cond)
+                .set_a_u32(8u32)
+                .link();
+            let a = b.clone().build();
+            let boxed = b.build_linked();
+            for a in [&*a, &*boxed] {
+                assert_eq!(a.a_u32, 8);
+                assert_eq!(a.a_u8, if cond { 7 } else { 0 });
+                assert_eq!(a.link_ptr().is_some(), cond);
+                assert_eq!(a.validate(), Ok(()));
+            }
+        }
+        // The last call wins
+        let a = AstructBuilder::new()
+            .set_a_u8(7)
+            .include_a_u8_if(false)
+            .include_a_u8_if(true)
+            .set_a_u32(8u32)
+            .link()
+            .build();
+        assert_eq!((a.a_u8, a.referenced_u32()), (7, Some(8)));
+    }
+
+    #[test]
+    fn from_existing_passes_fields_through() {
+        let old = AstructBuilder::new()
+            .set_a_u8(1)
+            .set_a_u32(2u32)
+            .link()
+            .build_linked();
+        let a = AstructBuilder::from_existing(old).link().build();
+        assert_eq!((a.a_u8, a.a_u32), (1, 2));
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn from_existing_overrides_and_relinks() {
+        let old = AstructBuilder::new()
+            .set_a_u8(1)
+            .set_a_u32(2u32)
+            .link()
+            .build_linked();
+        let a = AstructBuilder::from_existing(old)
+            .set_a_u8(5)
+            .link()
+            .build();
+        assert_eq!((a.a_u8, a.a_u32), (5, 2));
+        // The old box is gone, the link must be into the new one
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn from_existing_moves_non_copy_payload() {
+        let old = Box::new(Astruct::new(1, alloc::string::String::from("x")));
+        let a = AstructBuilder::from_existing(old).link().build();
+        assert_eq!(a.referenced_u32_ref().map(|s| s.as_str()), Some("x"));
+    }
+
+    #[test]
+    fn snapshot_restore_forks_a_base() {
+        let mut base = AstructBuilder::new().set_a_u8(1).set_a_u32(2u32);
+        let snap = base.snapshot();
+
+        let a = base.clone().set_a_u8(3).link().build();
+        assert_eq!((a.a_u8, a.a_u32), (3, 2));
+
+        base = base.set_a_u8(4).set_a_u32(5);
+        base.restore(snap);
+        let b = base.link().build();
+        assert_eq!((b.a_u8, b.a_u32), (1, 2));
+        // snap is Copy so it can restore again
+        let mut other = AstructBuilder::new().set_a_u32(9u32);
+        other.restore(snap);
+        assert_eq!(other.a_u32, Some(2));
+    }
+
+    #[test]
+    fn link_targets_a_u32_or_a_u8() {
+        let b = AstructBuilder::new().set_a_u8(0xab).set_a_u32(0x1234_5678);
+        let a = b.clone().link().build();
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!(a.referenced_u32(), Some(0x1234_5678));
+
+        let a = b.point_at_u8().build();
+        let (op_a_u8, op_a_u32) = a.link_ptrs();
+        assert!(ptr::eq(op_a_u8.unwrap().as_ptr(), &a.a_u8));
+        assert_eq!(op_a_u32, None);
+        // Reads a single byte, not the word that follows
+        assert_eq!(a.linked_a_u8(), Some(0xab));
+        assert_eq!(a.a_u32, 0x1234_5678);
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn point_at_u8_skipped_is_unlinked() {
+        let a = AstructBuilder::new()
+            .set_a_u8(1)
+            .include_a_u8_if(false)
+            .set_a_u32(2)
+            .point_at_u8()
+            .build();
+        assert_eq!((a.a_u8, a.link_ptrs()), (0, (None, None)));
+    }
+
+    #[test]
+    fn state_marker_adds_no_size() {
+        use core::mem::size_of;
+
+        // AstructBuilder's fields without the marker
+        #[allow(dead_code)]
+        struct Unmarked {
+            a_u8: Option<u8>,
+            a_u32: Option<u32>,
+            skip_a_u8: bool,
+            parent: (),
+        }
+
+        let size = size_of::<Unmarked>();
+        assert_eq!(size_of::<AstructBuilder<NeedsU32>>(), size);
+        assert_eq!(size_of::<AstructBuilder<Unlinked>>(), size);
+        assert_eq!(size_of::<AstructBuilder<Linked>>(), size);
+        assert_eq!(size_of::<AstructBuilder<LinkedU8>>(), size);
+    }
+
+    #[cfg(feature = "allocator_api")]
+    mod allocator_api {
+        use super::*;
+        use alloc::alloc::Global;
+        use core::alloc::{AllocError, Allocator, Layout};
+        use core::ptr::NonNull;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        // Counts live allocations, forwarding to Global
+        struct Counting<'a>(&'a AtomicUsize);
+
+        unsafe impl Allocator for Counting<'_> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.0.fetch_sub(1, Ordering::Relaxed);
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        #[test]
+        fn build_in_global() {
+            let a = AstructBuilder::new()
+                .set_a_u8(1)
+                .set_a_u32(2u32)
+                .link()
+                .build_in(Global);
+            assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        }
+
+        #[test]
+        fn build_in_custom_allocator() {
+            static LIVE: AtomicUsize = AtomicUsize::new(0);
+            let a = AstructBuilder::new()
+                .set_a_u32(3u32)
+                .link()
+                .build_in(Counting(&LIVE));
+            assert_eq!(LIVE.load(Ordering::Relaxed), 1);
+            assert_eq!(a.referenced_u32(), Some(3));
+            drop(a);
+            assert_eq!(LIVE.load(Ordering::Relaxed), 0);
+        }
+    }
+}
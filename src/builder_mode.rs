@@ -0,0 +1,51 @@
+//! Setter styles for builders generated by `#[derive(Builder)]`.
+//!
+//! The generated `<Name>Builder<.., M: BuildMode = Consuming>` has setters
+//! taking and returning `self` in [`Consuming`] mode, for chaining, and
+//! taking and returning `&mut self` in [`Mutable`] mode, for setting fields
+//! conditionally. `into_mutable()` and `into_consuming()` switch between
+//! the two and `build()` is available in both:
+//!
+//! ```
+//! use expr_builder_pattern::Builder;
+//!
+//! #[derive(Builder)]
+//! struct Point {
+//!     x: u32,
+//!     #[builder(optional)]
+//!     y: u32,
+//! }
+//!
+//! let p = PointBuilder::new().set_x(1).set_y(2).build().unwrap();
+//! assert_eq!((p.x, p.y), (1, 2));
+//!
+//! let mut b = PointBuilder::new().into_mutable();
+//! for i in 0..3 {
+//!     if i % 2 == 0 {
+//!         b.set_x(i);
+//!     }
+//! }
+//! let p = b.build().unwrap();
+//! assert_eq!((p.x, p.y), (2, 0));
+//! ```
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Selects the setter style of a generated builder, implemented only by
+/// [`Consuming`] and [`Mutable`].
+pub trait BuildMode: sealed::Sealed {}
+
+/// Setters take `self` and return `Self`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Consuming;
+
+/// Setters take `&mut self` and return `&mut Self`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Mutable;
+
+impl sealed::Sealed for Consuming {}
+impl sealed::Sealed for Mutable {}
+impl BuildMode for Consuming {}
+impl BuildMode for Mutable {}
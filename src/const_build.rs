@@ -0,0 +1,41 @@
+//! Building `Astruct`s in const context.
+//!
+//! A const value has no address of its own: every use of a `const` item
+//! is a fresh copy, and a `static` is placed by the linker. So there's
+//! nothing `op_a_u32` could point at while the value is being evaluated
+//! and it is always `None` here. Link a copy once it sits at its final
+//! address, e.g. with [`Astruct::link`] on a pinned box.
+
+use crate::selfref::Astruct;
+
+/// An unlinked `Astruct`, usable to initialize `const` and `static` items.
+///
+/// ```
+/// use expr_builder_pattern::const_build::new_astruct;
+/// use expr_builder_pattern::Astruct;
+///
+/// const DEFAULT: Astruct = new_astruct(1, 42);
+/// assert_eq!((DEFAULT.a_u8, DEFAULT.a_u32), (1, 42));
+/// assert_eq!(DEFAULT.link_ptr(), None);
+/// ```
+///
+/// `Astruct` reserves no values, so there is nothing to verify here. A
+/// protocol that does can check its rule on the const value, where a
+/// failing `assert!` is a compile error:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::const_build::new_astruct;
+/// use expr_builder_pattern::Astruct;
+///
+/// // This protocol reserves a_u8 == 0 for "no version"
+/// const fn versioned(a: Astruct) -> Astruct {
+///     assert!(a.a_u8 != 0, "a_u8: 0 is reserved for no version");
+///     a
+/// }
+///
+/// const BAD: Astruct = versioned(new_astruct(0, 42));
+/// # let _ = BAD;
+/// ```
+pub const fn new_astruct(a_u8: u8, a_u32: u32) -> Astruct {
+    Astruct::new(a_u8, a_u32)
+}
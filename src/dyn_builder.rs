@@ -0,0 +1,195 @@
+//! Boxing `repr(C)` structs that end in a flexible array member.
+
+use alloc::alloc::{alloc, handle_alloc_error};
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr::{self, addr_of_mut, NonNull};
+
+/// A header followed by a variable number of bytes, like a C struct with
+/// a trailing `uint8_t data[]`.
+#[repr(C)]
+pub struct DynFrame<H> {
+    pub header: H,
+    pub data: [u8],
+}
+
+impl<H: fmt::Debug> fmt::Debug for DynFrame<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynFrame")
+            .field("header", &self.header)
+            .field("data", &&self.data)
+            .finish()
+    }
+}
+
+/// Allocates a [`DynFrame<H>`] of a given total size in bytes.
+///
+/// `data` starts right after the header, `size_of::<H>()` is already a
+/// multiple of its alignment, and takes up the rest of the total.
+///
+/// ```
+/// use expr_builder_pattern::dyn_builder::DynBuilder;
+///
+/// let frame = DynBuilder::<u32>::new(8).unwrap().build(7, &[1, 2]);
+/// assert_eq!(frame.header, 7);
+/// assert_eq!(&frame.data, &[1, 2, 0, 0]);
+/// ```
+pub struct DynBuilder<H> {
+    layout: Layout,
+    _header: PhantomData<H>,
+}
+
+impl<H> DynBuilder<H> {
+    /// A builder for frames of `total` bytes, which must be at least the
+    /// header and a multiple of its alignment.
+    pub fn new(total: usize) -> Result<Self, AlignmentError> {
+        let err = AlignmentError {
+            total,
+            header_size: size_of::<H>(),
+            header_align: align_of::<H>(),
+        };
+        if total < size_of::<H>() || !total.is_multiple_of(align_of::<H>()) {
+            return Err(err);
+        }
+        let layout = Layout::from_size_align(total, align_of::<H>()).map_err(|_| err)?;
+        Ok(DynBuilder {
+            layout,
+            _header: PhantomData,
+        })
+    }
+
+    /// How many bytes `data` will hold.
+    pub fn data_len(&self) -> usize {
+        self.layout.size() - size_of::<H>()
+    }
+
+    /// Allocate the frame, write `header` at offset 0 then `data`, zero
+    /// filling what it leaves of the data bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `data` is longer than [`data_len`](DynBuilder::data_len).
+    pub fn build(self, header: H, data: &[u8]) -> Box<DynFrame<H>> {
+        let len = self.data_len();
+        assert!(
+            data.len() <= len,
+            "{} data bytes don't fit in {}",
+            data.len(),
+            len
+        );
+        let raw = if self.layout.size() == 0 {
+            NonNull::<H>::dangling().as_ptr().cast::<u8>()
+        } else {
+            let raw = unsafe { alloc(self.layout) };
+            if raw.is_null() {
+                handle_alloc_error(self.layout);
+            }
+            raw
+        };
+        // The slice length becomes the length of the trailing `data`
+        let p = ptr::slice_from_raw_parts_mut(raw, len) as *mut DynFrame<H>;
+        unsafe {
+            addr_of_mut!((*p).header).write(header);
+            let dst = addr_of_mut!((*p).data).cast::<u8>();
+            ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+            ptr::write_bytes(dst.add(data.len()), 0, len - data.len());
+            // Safety: allocated with the layout of a DynFrame<H> with len
+            // data bytes, which is self.layout, and fully initialized
+            Box::from_raw(p)
+        }
+    }
+}
+
+/// `total` bytes can't hold a header of `header_size` bytes aligned to
+/// `header_align`: it is smaller or not a multiple of the alignment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlignmentError {
+    pub total: usize,
+    pub header_size: usize,
+    pub header_align: usize,
+}
+
+impl fmt::Display for AlignmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes can't hold a {} byte header aligned to {}",
+            self.total, self.header_size, self.header_align
+        )
+    }
+}
+
+impl core::error::Error for AlignmentError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use core::mem::size_of_val;
+
+    #[repr(C)]
+    #[derive(Debug, PartialEq)]
+    struct Header {
+        kind: u16,
+        len: u64,
+    }
+
+    #[test]
+    fn data_follows_the_header() {
+        let b = DynBuilder::<Header>::new(24).unwrap();
+        assert_eq!(b.data_len(), 8);
+        let f = b.build(Header { kind: 1, len: 3 }, &[1, 2, 3]);
+        assert_eq!(f.header, Header { kind: 1, len: 3 });
+        assert_eq!(&f.data, &[1, 2, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(size_of_val(&*f), 24);
+        let base = &*f as *const DynFrame<Header> as *const u8;
+        assert_eq!(f.data.as_ptr() as usize - base as usize, 16);
+    }
+
+    #[test]
+    fn header_only_and_zero_sized() {
+        let f = DynBuilder::<u32>::new(4).unwrap().build(5, &[]);
+        assert_eq!((f.header, f.data.len()), (5, 0));
+        let f = DynBuilder::<()>::new(0).unwrap().build((), &[]);
+        assert!(f.data.is_empty());
+        let f = DynBuilder::<()>::new(3).unwrap().build((), b"abc");
+        assert_eq!(&f.data, b"abc");
+    }
+
+    #[test]
+    fn misaligned_or_too_small_totals() {
+        let err = DynBuilder::<Header>::new(20).err().unwrap();
+        assert_eq!(
+            err,
+            AlignmentError {
+                total: 20,
+                header_size: 16,
+                header_align: 8,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "20 bytes can't hold a 16 byte header aligned to 8"
+        );
+        assert!(DynBuilder::<Header>::new(8).is_err());
+    }
+
+    #[test]
+    fn owning_header() {
+        let total = size_of::<String>() + align_of::<String>();
+        let f = DynBuilder::<String>::new(total)
+            .unwrap()
+            .build("header".to_string(), &[9]);
+        assert_eq!(f.header, "header");
+        assert_eq!(f.data[0], 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "don't fit")]
+    fn too_much_data_panics() {
+        DynBuilder::<u32>::new(8).unwrap().build(0, &[0; 5]);
+    }
+}
@@ -0,0 +1,99 @@
+//! Building an enum whose variants are built by their own builders.
+
+use alloc::boxed::Box;
+use core::pin::Pin;
+
+use crate::builder::{AstructBuilder, Linked, NeedsU32};
+use crate::selfref::Astruct;
+use crate::EnumBuilder;
+
+/// Either a bare `u8` or a full linked [`Astruct`].
+///
+/// `Large` is boxed and pinned like every built `Astruct`, see
+/// [`AstructBuilder::build`].
+#[derive(Debug, EnumBuilder)]
+pub enum AstructKind {
+    Small(u8),
+    Large(Pin<Box<Astruct>>),
+}
+
+impl AstructKindBuilder {
+    /// Build the `Large` variant with an [`AstructBuilder`],
+    /// [`finish`](AstructBuilder::finish) stores it back in this builder.
+    ///
+    /// ```
+    /// use expr_builder_pattern::enum_builder::{AstructKind, AstructKindBuilder};
+    ///
+    /// let kind = AstructKindBuilder::new()
+    ///     .large()
+    ///     .set_a_u8(1)
+    ///     .set_a_u32(2)
+    ///     .link()
+    ///     .finish()
+    ///     .build()
+    ///     .unwrap();
+    /// let AstructKind::Large(a) = kind else { panic!() };
+    /// assert_eq!(a.referenced_u32(), Some(2));
+    /// ```
+    pub fn large(self) -> AstructBuilder<NeedsU32, u32, AstructKindBuilder> {
+        AstructBuilder::with_parent(self)
+    }
+}
+
+impl AstructBuilder<Linked, u32, AstructKindBuilder> {
+    /// Build the `Astruct` and select it as `AstructKind::Large`.
+    pub fn finish(self) -> AstructKindBuilder {
+        let (parent, built) = self.build_for_parent();
+        parent.set_large(built)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::BuildError;
+    use core::ptr;
+
+    #[test]
+    fn small_variant() {
+        let kind = AstructKindBuilder::new().set_small(3).build().unwrap();
+        assert!(matches!(kind, AstructKind::Small(3)));
+    }
+
+    #[test]
+    fn large_variant_is_linked() {
+        let kind = AstructKindBuilder::new()
+            .large()
+            .set_a_u8(1)
+            .set_a_u32(42)
+            .link()
+            .finish()
+            .build()
+            .unwrap();
+        let AstructKind::Large(a) = kind else {
+            panic!("expected Large, got {:?}", kind);
+        };
+        assert_eq!((a.a_u8, a.a_u32), (1, 42));
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn later_choice_wins() {
+        let kind = AstructKindBuilder::new()
+            .large()
+            .set_a_u32(1)
+            .link()
+            .finish()
+            .set_small(2)
+            .build()
+            .unwrap();
+        assert!(matches!(kind, AstructKind::Small(2)));
+    }
+
+    #[test]
+    fn no_variant_selected() {
+        let err = AstructKindBuilder::new().build().unwrap_err();
+        assert_eq!(err, BuildError::NoVariantSelected);
+        assert_eq!(err.to_string(), "no variant selected");
+    }
+}
@@ -0,0 +1,101 @@
+//! Errors shared across the crate.
+
+use core::fmt;
+
+/// Why a value could not be initialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UninitError {
+    /// The init closure panicked, the partially written value was discarded.
+    InitPanicked,
+}
+
+impl fmt::Display for UninitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UninitError::InitPanicked => write!(f, "init closure panicked"),
+        }
+    }
+}
+
+impl core::error::Error for UninitError {}
+
+/// The allocator returned null for a `size` byte allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "allocating {} bytes aligned to {} failed",
+            self.size, self.align
+        )
+    }
+}
+
+impl core::error::Error for AllocError {}
+
+/// A self reference that does not point at the field it should.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkError {
+    /// The pointer field that was checked.
+    pub field: &'static str,
+    /// Address of the target field in this instance.
+    pub expected: usize,
+    /// Address the pointer actually holds.
+    pub found: usize,
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} points to 0x{:x}, expected 0x{:x}",
+            self.field, self.found, self.expected
+        )
+    }
+}
+
+impl core::error::Error for LinkError {}
+
+/// Why a builder could not produce its value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// A required field was never set.
+    MissingField { name: &'static str },
+    /// An enum builder was built before any variant was chosen.
+    NoVariantSelected,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingField { name } => write!(f, "missing field `{}`", name),
+            BuildError::NoVariantSelected => write!(f, "no variant selected"),
+        }
+    }
+}
+
+impl core::error::Error for BuildError {}
+
+/// Why a value could not be decoded from bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Fewer bytes than the encoded layout needs.
+    TooShort { needed: usize, found: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TooShort { needed, found } => {
+                write!(f, "need {} bytes, found {}", needed, found)
+            }
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
@@ -0,0 +1,140 @@
+//! Arithmetic expressions over an [`Astruct`]'s `a_u32`.
+
+use alloc::boxed::Box;
+
+use crate::selfref::Astruct;
+
+/// An expression evaluated against an `Astruct`, see [`ExprBuilder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(u32),
+    /// The `a_u32` of the `Astruct` being evaluated.
+    Field,
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluate against `a` with wrapping arithmetic.
+    ///
+    /// [`Field`](Expr::Field) is read through `op_a_u32` when `a` is
+    /// linked, otherwise from `a_u32` directly.
+    pub fn eval(&self, a: &Astruct) -> u32 {
+        match self {
+            Expr::Const(n) => *n,
+            Expr::Field => a.referenced_u32().unwrap_or(a.a_u32),
+            Expr::Add(l, r) => l.eval(a).wrapping_add(r.eval(a)),
+            Expr::Mul(l, r) => l.eval(a).wrapping_mul(r.eval(a)),
+        }
+    }
+}
+
+/// Builds an [`Expr`] left to right, each operation applying to
+/// everything before it:
+///
+/// ```
+/// use expr_builder_pattern::{Astruct, ExprBuilder};
+///
+/// let a = Astruct::boxed_linked(0, 5);
+/// // (a_u32 + 10) * 2
+/// assert_eq!(ExprBuilder::new().field().add(10).mul(2).eval(&a), 30);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExprBuilder {
+    expr: Expr,
+}
+
+// add and mul take a u32, not another ExprBuilder as ops::Add would
+#[allow(clippy::should_implement_trait)]
+impl ExprBuilder {
+    /// Start from `Const(0)`.
+    pub fn new() -> Self {
+        ExprBuilder {
+            expr: Expr::Const(0),
+        }
+    }
+
+    /// Replace the expression so far with [`Expr::Field`].
+    pub fn field(mut self) -> Self {
+        self.expr = Expr::Field;
+        self
+    }
+
+    /// Replace the expression so far with [`Expr::Const`].
+    pub fn constant(mut self, n: u32) -> Self {
+        self.expr = Expr::Const(n);
+        self
+    }
+
+    pub fn add(self, n: u32) -> Self {
+        self.add_expr(Expr::Const(n))
+    }
+
+    pub fn mul(self, n: u32) -> Self {
+        self.mul_expr(Expr::Const(n))
+    }
+
+    pub fn add_expr(mut self, rhs: Expr) -> Self {
+        self.expr = Expr::Add(Box::new(self.expr), Box::new(rhs));
+        self
+    }
+
+    pub fn mul_expr(mut self, rhs: Expr) -> Self {
+        self.expr = Expr::Mul(Box::new(self.expr), Box::new(rhs));
+        self
+    }
+
+    pub fn build(self) -> Expr {
+        self.expr
+    }
+
+    /// Shorthand for `build().eval(a)`.
+    pub fn eval(&self, a: &Astruct) -> u32 {
+        self.expr.eval(a)
+    }
+}
+
+impl Default for ExprBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_add_mul() {
+        let a = Astruct::boxed_linked(1, 7);
+        let e = ExprBuilder::new().field().add(10).mul(2);
+        assert_eq!(e.eval(&a), (a.a_u32 + 10) * 2);
+        assert_eq!(
+            e.build(),
+            Expr::Mul(
+                Box::new(Expr::Add(Box::new(Expr::Field), Box::new(Expr::Const(10)))),
+                Box::new(Expr::Const(2)),
+            )
+        );
+    }
+
+    #[test]
+    fn field_times_field() {
+        let a = Astruct::new(0, 6u32);
+        let e = ExprBuilder::new().field().mul_expr(Expr::Field).add(1);
+        assert_eq!(e.eval(&a), 37);
+    }
+
+    #[test]
+    fn constants_and_empty() {
+        let a = Astruct::new(0, 6u32);
+        assert_eq!(ExprBuilder::new().eval(&a), 0);
+        assert_eq!(ExprBuilder::new().constant(3).add(4).eval(&a), 7);
+    }
+
+    #[test]
+    fn wraps_on_overflow() {
+        let a = Astruct::new(0, u32::MAX);
+        assert_eq!(ExprBuilder::new().field().add(2).eval(&a), 1);
+    }
+}
@@ -0,0 +1,107 @@
+//! A C interface to the linked `Astruct`.
+//!
+//! `Astruct` is `repr(C)`, so C can read its fields directly. The
+//! allocation is owned by Rust: create it with [`astruct_new_linked`] and
+//! release it with [`astruct_free`], never with `free()`.
+
+use alloc::boxed::Box;
+use core::pin::Pin;
+
+use crate::selfref::Astruct;
+
+/// Allocate an `Astruct` with `op_a_u32` pointing at its own `a_u32`.
+///
+/// The value must not be moved or copied out of the returned allocation,
+/// that would leave the link pointing at the old one.
+#[no_mangle]
+pub extern "C" fn astruct_new_linked(a_u8: u8, a_u32: u32) -> *mut Astruct {
+    // Safety: C only gets the pointer, the allocation never moves
+    Box::into_raw(unsafe { Pin::into_inner_unchecked(Astruct::boxed_linked(a_u8, a_u32)) })
+}
+
+/// Write the value `op_a_u32` points at to `out`.
+///
+/// Returns false, leaving `out` untouched, if either pointer is null or
+/// the struct is not linked to its own `a_u32`.
+///
+/// # Safety
+///
+/// Non-null `a` must come from [`astruct_new_linked`] and not have been
+/// freed, non-null `out` must be valid for writing a `u32`.
+#[no_mangle]
+pub unsafe extern "C" fn astruct_linked_value(a: *const Astruct, out: *mut u32) -> bool {
+    let (Some(a), false) = (a.as_ref(), out.is_null()) else {
+        return false;
+    };
+    if a.validate().is_err() {
+        return false;
+    }
+    match a.referenced_u32() {
+        Some(v) => {
+            out.write(v);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Drop an `Astruct` from [`astruct_new_linked`], null is a no-op.
+///
+/// # Safety
+///
+/// Non-null `a` must come from [`astruct_new_linked`] and is invalid
+/// afterwards, freeing it twice is undefined behavior.
+#[no_mangle]
+pub unsafe extern "C" fn astruct_free(a: *mut Astruct) {
+    if !a.is_null() {
+        drop(Box::from_raw(a));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn linked_value_across_raw_pointers() {
+        let a = astruct_new_linked(1, 42);
+        let mut out = 0;
+        assert!(unsafe { astruct_linked_value(a, &mut out) });
+        assert_eq!(out, 42);
+        // C can read the repr(C) fields in place
+        assert_eq!(unsafe { ((*a).a_u8, (*a).a_u32) }, (1, 42));
+        assert!(ptr::eq(
+            unsafe { (*a).link_ptr().unwrap().as_ptr() },
+            unsafe { &(*a).a_u32 }
+        ));
+        unsafe { astruct_free(a) };
+    }
+
+    #[test]
+    fn null_pointers_are_handled() {
+        let mut out = 7;
+        assert!(!unsafe { astruct_linked_value(ptr::null(), &mut out) });
+        assert_eq!(out, 7);
+        let a = astruct_new_linked(1, 42);
+        assert!(!unsafe { astruct_linked_value(a, ptr::null_mut()) });
+        unsafe { astruct_free(a) };
+        unsafe { astruct_free(ptr::null_mut()) };
+    }
+
+    #[test]
+    fn unlinked_value_is_not_reported() {
+        let a = Box::into_raw(Astruct::boxed_unlinked(1, 2u32));
+        let mut out = 0;
+        assert!(!unsafe { astruct_linked_value(a, &mut out) });
+        unsafe { astruct_free(a) };
+    }
+
+    #[test]
+    fn free_releases_and_ignores_null() {
+        // A double free or leak here shows up under Miri
+        let a = astruct_new_linked(1, 2);
+        unsafe { astruct_free(a) };
+        unsafe { astruct_free(ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,50 @@
+//! Normalizing values on their way into a builder.
+
+/// Turns the value passed to a setter into the one stored, e.g. rounding
+/// `a_u32` down to a multiple of 4.
+///
+/// Implemented for every `Fn(I) -> O`, so a plain function works as well
+/// as a dedicated type. `#[derive(Builder)]` applies one to a field with
+/// `#[builder(map = path)]`, where `path` names a function or a unit
+/// struct implementing this trait.
+pub trait FieldMapper<I, O> {
+    fn map(&self, val: I) -> O;
+}
+
+impl<I, O, F: Fn(I) -> O> FieldMapper<I, O> for F {
+    fn map(&self, val: I) -> O {
+        self(val)
+    }
+}
+
+/// Rounds down to a multiple of `N`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoundDown<const N: u32>;
+
+impl<const N: u32> FieldMapper<u32, u32> for RoundDown<N> {
+    fn map(&self, val: u32) -> u32 {
+        val - val % N
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn double(v: u32) -> u64 {
+        u64::from(v) * 2
+    }
+
+    #[test]
+    fn functions_and_closures_map() {
+        assert_eq!(double.map(3), 6);
+        assert_eq!((|v: u8| v + 1).map(1), 2);
+    }
+
+    #[test]
+    fn round_down() {
+        assert_eq!(RoundDown::<4>.map(7), 4);
+        assert_eq!(RoundDown::<4>.map(8), 8);
+        assert_eq!(RoundDown::<4>.map(3), 0);
+    }
+}
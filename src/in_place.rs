@@ -0,0 +1,78 @@
+//! One place for the `new_uninit`, initialize, `assume_init` sequence.
+
+use alloc::boxed::Box;
+
+/// Types that can initialize themselves inside uninitialized memory,
+/// including self references to their final address.
+///
+/// # Safety
+///
+/// `init_in_place` must initialize every field of `*slot`, which
+/// [`boxed_in_place`] relies on when it assumes the value initialized.
+pub unsafe trait InPlaceInit {
+    type Args;
+
+    /// Initialize `*slot` from `args`.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must be valid for writes and its memory must not move
+    /// afterwards if the value is self-referential. Nothing may read it
+    /// before this returns.
+    unsafe fn init_in_place(slot: *mut Self, args: Self::Args);
+}
+
+/// Box a `T` initialized in place from `args`.
+///
+/// ```
+/// use expr_builder_pattern::in_place::boxed_in_place;
+/// use expr_builder_pattern::Astruct;
+///
+/// let a = boxed_in_place::<Astruct>((1, 2));
+/// assert_eq!(a.referenced_u32(), Some(2));
+/// ```
+pub fn boxed_in_place<T: InPlaceInit>(args: T::Args) -> Box<T> {
+    let mut b = Box::<T>::new_uninit();
+    unsafe {
+        // Safety: freshly allocated on the heap, where it stays
+        T::init_in_place(b.as_mut_ptr(), args);
+        // Safety: the InPlaceInit contract
+        b.assume_init()
+    }
+}
+
+// Safety: a plain write initializes it
+unsafe impl InPlaceInit for u32 {
+    type Args = u32;
+
+    unsafe fn init_in_place(slot: *mut u32, v: u32) {
+        slot.write(v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selfref::{Astruct, Xstruct};
+    use core::pin::Pin;
+    use core::ptr;
+
+    #[test]
+    fn plain_u32() {
+        assert_eq!(*boxed_in_place::<u32>(5), 5);
+    }
+
+    #[test]
+    fn astruct_is_linked() {
+        let a = boxed_in_place::<Astruct<u64>>((1, 2));
+        assert_eq!((a.a_u8, a.a_u32), (1, 2));
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn xstruct_is_linked() {
+        let x = Box::into_pin(boxed_in_place::<Xstruct>(3));
+        assert_eq!(x.validate(), Ok(()));
+        assert_eq!((Pin::as_ref(&x).f(), *x.as_ref().p()), (3, 3));
+    }
+}
@@ -0,0 +1,112 @@
+//! A `MaybeUninit<T>` that knows whether it has been written.
+
+use core::mem::MaybeUninit;
+
+/// Storage for a `T` that is written once before being read.
+///
+/// Reading an uninitialized `MaybeUninit<Vec<u32>>` as a `Vec` yields a
+/// garbage length at best. `InitOnce` tracks initialization so reads
+/// before the write return `None` or panic instead.
+pub struct InitOnce<T> {
+    value: MaybeUninit<T>,
+    initialized: bool,
+}
+
+impl<T> InitOnce<T> {
+    pub const fn new() -> Self {
+        InitOnce {
+            value: MaybeUninit::uninit(),
+            initialized: false,
+        }
+    }
+
+    /// Store `val`, dropping any previous value, and return it.
+    pub fn write(&mut self, val: T) -> &mut T {
+        if self.initialized {
+            self.initialized = false;
+            // Safety: the flag was set so the value is initialized.
+            unsafe { self.value.assume_init_drop() }
+        }
+        let v = self.value.write(val);
+        self.initialized = true;
+        v
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        // Safety: the flag is only set after the value is written.
+        self.initialized
+            .then(|| unsafe { self.value.assume_init_ref() })
+    }
+
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        // Safety: the flag is only set after the value is written.
+        self.initialized
+            .then(|| unsafe { self.value.assume_init_mut() })
+    }
+
+    /// The value, like `MaybeUninit::assume_init_ref` but checked.
+    ///
+    /// # Panics
+    ///
+    /// If nothing has been written yet.
+    pub fn assume_init_ref(&self) -> &T {
+        self.get().expect("InitOnce read before it was written")
+    }
+}
+
+impl<T> Default for InitOnce<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for InitOnce<T> {
+    fn drop(&mut self) {
+        if self.initialized {
+            // Safety: the flag was set so the value is initialized.
+            unsafe { self.value.assume_init_drop() }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn empty_until_written() {
+        let mut v = InitOnce::<Vec<u32>>::new();
+        assert!(v.get().is_none());
+        assert!(v.get_mut().is_none());
+        v.write(Vec::new()).push(1);
+        assert_eq!(v.get().map(Vec::len), Some(1));
+        v.get_mut().unwrap().push(2);
+        assert_eq!(v.assume_init_ref(), &[1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "InitOnce read before it was written")]
+    fn reading_before_writing_panics() {
+        let v = InitOnce::<Vec<u32>>::new();
+        // With a plain MaybeUninit this would read garbage
+        v.assume_init_ref().len();
+    }
+
+    #[test]
+    fn drops_written_value_once() {
+        let rc = Rc::new(());
+        let mut v = InitOnce::new();
+        v.write(rc.clone());
+        v.write(rc.clone());
+        assert_eq!(Rc::strong_count(&rc), 2);
+        drop(v);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        drop(InitOnce::<Rc<()>>::new());
+    }
+}
@@ -0,0 +1,433 @@
+//! A doubly linked list whose nodes point at each other.
+
+use alloc::boxed::Box;
+use core::marker::PhantomPinned;
+use core::pin::Pin;
+use core::ptr::{addr_of_mut, NonNull};
+
+/// A list node, `prev` and `next` point at the neighbouring nodes'
+/// allocations.
+///
+/// Like `Astruct` the pointers are only valid while the nodes they target
+/// stay put, so nodes are pinned and handed out as `Pin<&Node>`.
+#[derive(Debug)]
+pub struct Node {
+    pub value: u32,
+    pub(crate) prev: Option<NonNull<Node>>,
+    pub(crate) next: Option<NonNull<Node>>,
+    _pin: PhantomPinned,
+}
+
+impl Node {
+    /// Box a node between `prev` and `next`, written field by field into
+    /// the `Box::new_uninit` allocation.
+    fn boxed(
+        value: u32,
+        prev: Option<NonNull<Node>>,
+        next: Option<NonNull<Node>>,
+    ) -> Pin<Box<Node>> {
+        let mut b = Box::<Node>::new_uninit();
+        let p = b.as_mut_ptr();
+        // Safety: every field is written before assume_init
+        unsafe {
+            addr_of_mut!((*p).value).write(value);
+            addr_of_mut!((*p).prev).write(prev);
+            addr_of_mut!((*p).next).write(next);
+            addr_of_mut!((*p)._pin).write(PhantomPinned);
+            Box::into_pin(b.assume_init())
+        }
+    }
+
+    /// The value can change in place, only the node's address matters to
+    /// its neighbours.
+    pub fn value_mut(self: Pin<&mut Self>) -> &mut u32 {
+        // Safety: value isn't structurally pinned, nothing is moved
+        unsafe { &mut self.get_unchecked_mut().value }
+    }
+}
+
+/// Owns a chain of pinned boxed [`Node`]s linked through `prev` and
+/// `next`.
+///
+/// Each node is a `Pin<Box<Node>>` turned into a raw pointer while in the
+/// list and back into one when it is removed. Dropping pops node after
+/// node, so a long list doesn't recurse.
+///
+/// A [`Cursor`] or [`CursorMut`] sits on a node or on the "ghost"
+/// position past the tail and before the head, where moving next goes to
+/// the head and moving prev to the tail.
+pub struct IntrusiveList {
+    head: Option<NonNull<Node>>,
+    tail: Option<NonNull<Node>>,
+    len: usize,
+}
+
+impl IntrusiveList {
+    pub fn new() -> Self {
+        IntrusiveList {
+            head: None,
+            tail: None,
+            len: 0,
+        }
+    }
+
+    pub fn push_front(&mut self, value: u32) {
+        self.insert_between(None, self.head, value);
+    }
+
+    /// Take the first node out of the list, its links cleared.
+    pub fn pop_front(&mut self) -> Option<Pin<Box<Node>>> {
+        let head = self.head?;
+        // Safety: head is in this list
+        Some(unsafe { self.unlink(head) })
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            next: self.head,
+            _list: self,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn cursor_front(&self) -> Cursor<'_> {
+        Cursor {
+            current: self.head,
+            list: self,
+        }
+    }
+
+    pub fn cursor_back(&self) -> Cursor<'_> {
+        Cursor {
+            current: self.tail,
+            list: self,
+        }
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_> {
+        CursorMut {
+            current: self.head,
+            list: self,
+        }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_> {
+        CursorMut {
+            current: self.tail,
+            list: self,
+        }
+    }
+
+    /// Link a new node between the adjacent `prev` and `next`, `None`
+    /// meaning the list's end.
+    fn insert_between(
+        &mut self,
+        prev: Option<NonNull<Node>>,
+        next: Option<NonNull<Node>>,
+        value: u32,
+    ) {
+        let node = Node::boxed(value, prev, next);
+        // Safety: the node is only turned back into a pinned box by unlink
+        let node = NonNull::from(Box::leak(unsafe { Pin::into_inner_unchecked(node) }));
+        // Safety: prev and next are nodes of this list, no reference to
+        // them is alive while the list is borrowed mutably
+        unsafe {
+            match prev {
+                Some(p) => (*p.as_ptr()).next = Some(node),
+                None => self.head = Some(node),
+            }
+            match next {
+                Some(n) => (*n.as_ptr()).prev = Some(node),
+                None => self.tail = Some(node),
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Remove `node`, joining its neighbours.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be in this list.
+    unsafe fn unlink(&mut self, node: NonNull<Node>) -> Pin<Box<Node>> {
+        // Safety: nodes came from Box::leak in insert_between and are
+        // owned by the list alone
+        let mut node = Box::from_raw(node.as_ptr());
+        let (prev, next) = (node.prev.take(), node.next.take());
+        match prev {
+            Some(p) => (*p.as_ptr()).next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(n) => (*n.as_ptr()).prev = prev,
+            None => self.tail = prev,
+        }
+        self.len -= 1;
+        Box::into_pin(node)
+    }
+}
+
+impl Default for IntrusiveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for IntrusiveList {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+/// Front to back iterator over an [`IntrusiveList`].
+pub struct Iter<'a> {
+    next: Option<NonNull<Node>>,
+    _list: &'a IntrusiveList,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Pin<&'a Node>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: nodes live as long as the borrowed list and never move
+        let node = unsafe { Pin::new_unchecked(self.next?.as_ref()) };
+        self.next = node.next;
+        Some(node)
+    }
+}
+
+/// Step from `current` in either direction, through the ghost position.
+fn step(
+    list: &IntrusiveList,
+    current: Option<NonNull<Node>>,
+    forward: bool,
+) -> Option<NonNull<Node>> {
+    match current {
+        // Safety: current is a node of list
+        Some(c) => unsafe {
+            match forward {
+                true => c.as_ref().next,
+                false => c.as_ref().prev,
+            }
+        },
+        None if forward => list.head,
+        None => list.tail,
+    }
+}
+
+/// A read only position in an [`IntrusiveList`].
+pub struct Cursor<'a> {
+    current: Option<NonNull<Node>>,
+    list: &'a IntrusiveList,
+}
+
+impl<'a> Cursor<'a> {
+    /// The node under the cursor, `None` at the ghost position.
+    pub fn current(&self) -> Option<Pin<&'a Node>> {
+        // Safety: nodes live as long as the borrowed list and never move
+        self.current
+            .map(|c| unsafe { Pin::new_unchecked(&*c.as_ptr()) })
+    }
+
+    pub fn move_next(&mut self) {
+        self.current = step(self.list, self.current, true);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.current = step(self.list, self.current, false);
+    }
+}
+
+/// A position in an [`IntrusiveList`] that can insert and remove nodes.
+pub struct CursorMut<'a> {
+    current: Option<NonNull<Node>>,
+    list: &'a mut IntrusiveList,
+}
+
+impl CursorMut<'_> {
+    /// The node under the cursor, `None` at the ghost position.
+    pub fn current(&mut self) -> Option<Pin<&mut Node>> {
+        // Safety: the list is borrowed mutably and nodes never move
+        self.current
+            .map(|c| unsafe { Pin::new_unchecked(&mut *c.as_ptr()) })
+    }
+
+    pub fn move_next(&mut self) {
+        self.current = step(self.list, self.current, true);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.current = step(self.list, self.current, false);
+    }
+
+    /// Insert `value` after the current node, or at the front from the
+    /// ghost position. The cursor stays where it is.
+    pub fn insert_after(&mut self, value: u32) {
+        let next = step(self.list, self.current, true);
+        self.list.insert_between(self.current, next, value);
+    }
+
+    /// Remove the current node and move on to the next one. Returns
+    /// `None` and does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<Pin<Box<Node>>> {
+        let current = self.current?;
+        self.current = step(self.list, self.current, true);
+        // Safety: current is a node of this list
+        Some(unsafe { self.list.unlink(current) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn forward(list: &IntrusiveList) -> Vec<u32> {
+        list.iter().map(|n| n.value).collect()
+    }
+
+    fn backward(list: &IntrusiveList) -> Vec<u32> {
+        let mut values = Vec::new();
+        let mut c = list.cursor_back();
+        while let Some(n) = c.current() {
+            values.push(n.value);
+            c.move_prev();
+        }
+        values
+    }
+
+    #[test]
+    fn push_iter_pop() {
+        let mut list = IntrusiveList::new();
+        assert!(list.is_empty());
+        for v in 1..=3 {
+            list.push_front(v);
+        }
+        assert_eq!(list.len(), 3);
+        assert_eq!(forward(&list), [3, 2, 1]);
+        assert_eq!(backward(&list), [1, 2, 3]);
+
+        let first = list.pop_front().unwrap();
+        assert_eq!((first.value, first.prev, first.next), (3, None, None));
+        assert_eq!(list.iter().next().unwrap().prev, None);
+        assert_eq!(list.len(), 2);
+        while list.pop_front().is_some() {}
+        assert!(list.is_empty());
+        assert!(list.pop_front().is_none());
+        assert!(list.cursor_back().current().is_none());
+    }
+
+    #[test]
+    fn links_point_at_the_neighbours() {
+        let mut list = IntrusiveList::default();
+        list.push_front(1);
+        list.push_front(2);
+        let nodes: Vec<Pin<&Node>> = list.iter().collect();
+        let next = nodes[0].next.unwrap().as_ptr() as *const Node;
+        let prev = nodes[1].prev.unwrap().as_ptr() as *const Node;
+        assert!(core::ptr::eq(next, &*nodes[1]));
+        assert!(core::ptr::eq(prev, &*nodes[0]));
+        assert_eq!((nodes[0].prev, nodes[1].next), (None, None));
+    }
+
+    #[test]
+    fn cursor_moves_through_the_ghost() {
+        let mut list = IntrusiveList::new();
+        list.push_front(2);
+        list.push_front(1);
+        let mut c = list.cursor_front();
+        assert_eq!(c.current().unwrap().value, 1);
+        c.move_next();
+        c.move_next();
+        assert!(c.current().is_none());
+        c.move_next();
+        assert_eq!(c.current().unwrap().value, 1);
+        c.move_prev();
+        c.move_prev();
+        assert_eq!(c.current().unwrap().value, 2);
+    }
+
+    #[test]
+    fn cursor_edits_at_the_ends() {
+        let mut list = IntrusiveList::new();
+        let mut c = list.cursor_front_mut();
+        // Empty list, inserting from the ghost makes a head and tail
+        assert!(c.remove_current().is_none());
+        c.insert_after(1);
+        c.move_next();
+        c.insert_after(3);
+        c.insert_after(2);
+        *c.current().unwrap().value_mut() = 10;
+        assert_eq!(forward(&list), [10, 2, 3]);
+
+        // Removing the head moves on to the new head
+        let mut c = list.cursor_front_mut();
+        assert_eq!(c.remove_current().unwrap().value, 10);
+        assert_eq!(c.current().unwrap().value, 2);
+        // Removing the tail moves on to the ghost
+        let mut c = list.cursor_back_mut();
+        assert_eq!(c.remove_current().unwrap().value, 3);
+        assert!(c.current().is_none());
+        assert_eq!((forward(&list), backward(&list)), (vec![2], vec![2]));
+        // Removing the last node empties both ends
+        let mut c = list.cursor_back_mut();
+        let last = c.remove_current().unwrap();
+        assert_eq!((last.prev, last.next), (None, None));
+        assert!(list.is_empty());
+        assert!(list.cursor_back().current().is_none());
+    }
+
+    #[test]
+    fn random_edits_agree_in_both_directions() {
+        // xorshift, any fixed sequence will do
+        let mut state = 0x2545_f491_u32;
+        let mut random = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize % n
+        };
+        let mut list = IntrusiveList::new();
+        let mut model: Vec<u32> = Vec::new();
+        for v in 0..2000 {
+            // The cursor's index in the model, model.len() is the ghost
+            let at = random(model.len() + 1);
+            let mut c = list.cursor_front_mut();
+            for _ in 0..at {
+                c.move_next();
+            }
+            if random(3) == 0 {
+                let removed = c.remove_current().map(|n| n.value);
+                let expected = (at < model.len()).then(|| model.remove(at));
+                assert_eq!(removed, expected);
+            } else {
+                c.insert_after(v);
+                model.insert(if at == model.len() { 0 } else { at + 1 }, v);
+            }
+            assert_eq!(list.len(), model.len());
+            assert_eq!(forward(&list), model);
+            let mut reversed = model.clone();
+            reversed.reverse();
+            assert_eq!(backward(&list), reversed);
+        }
+    }
+
+    #[test]
+    fn dropping_a_long_list_does_not_recurse() {
+        let mut list = IntrusiveList::new();
+        for v in 0..100_000 {
+            list.push_front(v);
+        }
+        assert_eq!(list.len(), 100_000);
+        assert_eq!(list.iter().count(), 100_000);
+        drop(list);
+    }
+}
@@ -0,0 +1,144 @@
+//! Experiments with self-referential structs built via `Box::new_uninit`
+//! and builders that hide the unsafe initialization.
+//!
+//! Only `core` and `alloc` are needed with the default `std` feature
+//! disabled, which drops the helpers that catch panics. Build with
+//! `--no-default-features --features no_std`, e.g. for
+//! `thumbv7em-none-eabi`. `no_std` is rejected together with `std`.
+//!
+//! None of the public API is meant to be UB. The unit and integration
+//! tests pass under `cargo +nightly miri test --lib --tests` with the
+//! default Stacked Borrows model and no extra flags, `tests/miri.rs`
+//! covers the builder, `SelfRef`, arena and stack paths with fewer
+//! iterations. The doctests haven't been run under Miri.
+//!
+//! # Thread safety
+//!
+//! A self pointer targets memory owned by the same value, so it doesn't
+//! stop that value from moving to or being shared with another thread.
+//! `Astruct<T>` is `Send`/`Sync` when `T` is, and `Astruct2`,
+//! `AstructRel`, `OwnedSlice`, `VecView` and `Xstruct` are both, as is
+//! `AstructKind` through its fields. A pinned box keeps
+//! its link when sent, `Pin` rather than `Send` is what prevents the
+//! moves that would break it. `AstructSharedSync` is `Send + Sync` for
+//! `T: Send + Sync`, while `AstructShared` is neither since `Rc` isn't:
+//!
+//! ```compile_fail
+//! use expr_builder_pattern::AstructShared;
+//!
+//! let a = AstructShared::new_linked(1, 2u32);
+//! std::thread::spawn(move || a.referenced_u32());
+//! ```
+//!
+//! Nor is an `Astruct` of a type that can't be shared:
+//!
+//! ```compile_fail
+//! use std::cell::Cell;
+//! use expr_builder_pattern::Astruct;
+//!
+//! fn sync<T: Sync>(_: &T) {}
+//! sync(&Astruct::boxed_linked(1, Cell::new(2u32)));
+//! ```
+//!
+//! `Arena`, `AstructArena`, `IntrusiveList`, `MultiSelfRef` and
+//! `SelfRefBox` hold raw pointers to memory they manage themselves and are
+//! left `!Send` and `!Sync`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+#[cfg(all(feature = "std", feature = "no_std"))]
+compile_error!(
+    "the `no_std` feature needs `std` disabled: build with \
+    `--no-default-features --features no_std`"
+);
+
+// Lets the derive macros refer to `::expr_builder_pattern` from inside
+// this crate as well.
+extern crate self as expr_builder_pattern;
+
+pub mod arena;
+pub mod async_builder;
+pub mod builder;
+pub mod builder_mode;
+pub mod const_build;
+pub mod dyn_builder;
+pub mod enum_builder;
+pub mod errors;
+pub mod expr;
+pub mod ffi;
+pub mod field_mapper;
+pub mod in_place;
+pub mod init_once;
+pub mod intrusive_list;
+pub mod macros;
+pub mod maybe_uninit_ext;
+pub mod merge;
+pub mod multi_self_ref;
+pub mod nested_builder;
+pub mod no_alloc_builder;
+pub mod owned_slice;
+pub mod partial_init;
+pub mod pin_drop;
+pub mod pin_project;
+pub mod pinned_vec;
+pub mod pool;
+pub mod rel_ptr;
+pub mod self_ref;
+pub mod self_ref_box;
+pub mod self_ref_clone;
+pub mod self_ref_pin;
+pub mod selfref;
+pub mod shared;
+pub mod uninit_box;
+pub mod validator;
+pub mod vec_view;
+pub mod zeroable;
+
+pub use arena::{Arena, AstructArena};
+pub use async_builder::AsyncAstructBuilder;
+pub use builder::{AstructBuilder, AstructBuilderSnapshot, Linked, LinkedU8, NeedsU32, Unlinked};
+pub use builder_mode::{BuildMode, Consuming, Mutable};
+pub use errors::{AllocError, BuildError, LinkError, ParseError, UninitError};
+pub use expr::{Expr, ExprBuilder};
+pub use expr_builder_pattern_derive::{Builder, EnumBuilder};
+pub use field_mapper::{FieldMapper, RoundDown};
+pub use in_place::{boxed_in_place, InPlaceInit};
+pub use init_once::InitOnce;
+pub use intrusive_list::{IntrusiveList, Node};
+pub use maybe_uninit_ext::CheckedWrite;
+pub use merge::MergeState;
+pub use multi_self_ref::{MultiSelfRef, OutOfBoundsRef};
+pub use owned_slice::{OwnedSlice, RangeError};
+pub use partial_init::{PartialInit, PartialInitError};
+pub use pin_drop::{PinDrop, PinDropWrapper};
+pub use pinned_vec::PinnedVec;
+pub use pool::{AstructPool, PoolGuard};
+pub use rel_ptr::RelPtr;
+pub use self_ref::{SelfRef, SelfRefBuilder};
+pub use self_ref_box::SelfRefBox;
+pub use self_ref_clone::SelfRefClone;
+pub use self_ref_pin::SelfRefPin;
+pub use selfref::{
+    astruct_offsets, boxed_u32, try_build_astruct, Astruct, Astruct2, AstructOffsets,
+    AstructRecord, AstructRel, LayoutInfo, Xstruct, XstructProjection,
+};
+#[cfg(feature = "std")]
+pub use selfref::{try_init_boxed, try_init_vec};
+pub use shared::{AstructShared, AstructSharedSync};
+pub use uninit_box::{UninitBox, UninitDrop};
+pub use validator::{SelfRefValidator, ValidationError, Validator};
+pub use vec_view::VecView;
+pub use zeroable::Zeroable;
+
+// Paths used by the derive macros
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+}
@@ -0,0 +1,188 @@
+//! Macros for writing the fields of a `#[repr(C)]` struct in place.
+
+use crate::in_place::InPlaceInit;
+use crate::selfref::Xstruct;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+
+/// Write fields of a `#[repr(C)]` struct through a raw pointer in
+/// declaration order.
+///
+/// Expands to one `addr_of_mut!(..).write(..)` per field in the order
+/// given, plus a compile time check that this order is strictly
+/// increasing in `offset_of!`. Fields listed out of order, or a listed
+/// field that no longer exists, fail to build. Only the listed fields are
+/// checked: a field left out, wherever it sits, is not detected and stays
+/// uninitialized for the caller to write. Like `ptr::write` it must be
+/// used in an `unsafe` block and `ptr` must be valid for writes.
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use expr_builder_pattern::repr_c_ordered_build;
+///
+/// #[repr(C)]
+/// struct Pair {
+///     a: u8,
+///     b: u32,
+/// }
+///
+/// let mut uninit = MaybeUninit::<Pair>::uninit();
+/// let pair = unsafe {
+///     repr_c_ordered_build!(uninit.as_mut_ptr() => Pair { a: 1, b: 2 });
+///     uninit.assume_init()
+/// };
+/// assert_eq!((pair.a, pair.b), (1, 2));
+/// ```
+///
+/// Listing `b` before `a` is a compile error:
+///
+/// ```compile_fail
+/// # use core::mem::MaybeUninit;
+/// # use expr_builder_pattern::repr_c_ordered_build;
+/// # #[repr(C)]
+/// # struct Pair {
+/// #     a: u8,
+/// #     b: u32,
+/// # }
+/// let mut uninit = MaybeUninit::<Pair>::uninit();
+/// unsafe { repr_c_ordered_build!(uninit.as_mut_ptr() => Pair { b: 2, a: 1 }) };
+/// ```
+#[macro_export]
+macro_rules! repr_c_ordered_build {
+    ($ptr:expr => $ty:ty { $($field:ident : $val:expr),+ $(,)? }) => {{
+        let p: *mut $ty = $ptr;
+        const {
+            $crate::macros::assert_ascending(&[$(::core::mem::offset_of!($ty, $field)),+])
+        };
+        $(::core::ptr::addr_of_mut!((*p).$field).write($val);)+
+    }};
+}
+
+/// Panics, at compile time when used in a `const` block, unless
+/// `offsets` is strictly increasing.
+#[doc(hidden)]
+pub const fn assert_ascending(offsets: &[usize]) {
+    let mut i = 1;
+    while i < offsets.len() {
+        assert!(
+            offsets[i - 1] < offsets[i],
+            "fields must be listed in increasing offset order"
+        );
+        i += 1;
+    }
+}
+
+/// Declare a pinned, linked [`Xstruct`](crate::Xstruct) on the stack.
+///
+/// `stack_self_ref!(let x = f);` binds `x: Pin<&mut Xstruct>` with its
+/// value set to `f` and `p()` reading it, without allocating. The storage is
+/// a `MaybeUninit<Xstruct>` local hidden by the macro, so like
+/// [`pin!`](core::pin::pin) nothing can move it and the pin can't outlive
+/// the enclosing scope.
+///
+/// ```
+/// use expr_builder_pattern::stack_self_ref;
+///
+/// stack_self_ref!(let x = 47);
+/// assert_eq!(x.as_ref().f(), 47);
+/// assert_eq!(*x.as_ref().p(), 47);
+/// assert_eq!(x.validate(), Ok(()));
+/// ```
+///
+/// Returning the pin from the function that declared it doesn't compile:
+///
+/// ```compile_fail
+/// use core::pin::Pin;
+/// use expr_builder_pattern::{stack_self_ref, Xstruct};
+///
+/// fn escape() -> Pin<&'static mut Xstruct> {
+///     stack_self_ref!(let x = 47);
+///     x
+/// }
+/// ```
+#[macro_export]
+macro_rules! stack_self_ref {
+    (let $name:ident = $f:expr $(;)?) => {
+        let f: u32 = $f;
+        let mut slot = ::core::mem::MaybeUninit::uninit();
+        // SAFETY: `slot` can't be named outside this macro so it is never
+        // moved, and an `Xstruct` has nothing to drop.
+        let $name = unsafe { $crate::macros::init_stack_xstruct(&mut slot, f) };
+    };
+}
+
+/// Link an `Xstruct` inside `slot`, the body of [`stack_self_ref!`].
+///
+/// The pin borrows `slot`, which ties it, and with it the link, to the
+/// slot.
+///
+/// # Safety
+///
+/// `slot` must not be moved or reused for as long as it is alive.
+#[doc(hidden)]
+pub unsafe fn init_stack_xstruct(slot: &mut MaybeUninit<Xstruct>, f: u32) -> Pin<&mut Xstruct> {
+    unsafe {
+        Xstruct::init_in_place(slot.as_mut_ptr(), f);
+        Pin::new_unchecked(slot.assume_init_mut())
+    }
+}
+
+/// Print the address of a struct and of each listed field together with
+/// its offset, for looking at the layout of any value.
+///
+/// The first argument is the struct as a place, so dereference boxes and
+/// pins: `dump_addresses!(*boxed, a_u8, a_u32)`. With `to w;` in front
+/// the lines are written to `w`, a `core::fmt::Write` or `std::io::Write`,
+/// instead of printed, and the macro evaluates to the `write!` result.
+///
+/// ```
+/// use core::fmt::Write;
+/// use expr_builder_pattern::{dump_addresses, Astruct};
+///
+/// let x = Astruct::boxed_linked(1, 2u32);
+/// let mut out = String::new();
+/// dump_addresses!(to out; *x, a_u8, a_u32).unwrap();
+/// // *x @ 0x5581c3d0aba0
+/// //   a_u8     @ 0x5581c3d0aba0 +0
+/// //   a_u32    @ 0x5581c3d0aba4 +4
+/// let lines: Vec<_> = out.lines().collect();
+/// assert_eq!(lines.len(), 3);
+/// assert!(lines[0].starts_with("*x @ 0x"));
+/// assert!(lines[1].starts_with("  a_u8     @ 0x") && lines[1].ends_with(" +0"));
+/// assert!(lines[2].ends_with(" +4"));
+/// ```
+#[macro_export]
+macro_rules! dump_addresses {
+    (to $w:expr; $base:expr, $($field:ident),+ $(,)?) => {{
+        let base = &$base;
+        let start = base as *const _ as *const u8 as usize;
+        let r = writeln!($w, "{} @ {:p}", stringify!($base), base);
+        $(
+            let r = r.and_then(|()| {
+                let field = &base.$field;
+                writeln!(
+                    $w,
+                    "  {:<8} @ {:p} +{}",
+                    stringify!($field),
+                    field,
+                    field as *const _ as *const u8 as usize - start
+                )
+            });
+        )+
+        r
+    }};
+    ($base:expr, $($field:ident),+ $(,)?) => {{
+        let base = &$base;
+        let start = base as *const _ as *const u8 as usize;
+        ::std::println!("{} @ {:p}", stringify!($base), base);
+        $(
+            let field = &base.$field;
+            ::std::println!(
+                "  {:<8} @ {:p} +{}",
+                stringify!($field),
+                field,
+                field as *const _ as *const u8 as usize - start
+            );
+        )+
+    }};
+}
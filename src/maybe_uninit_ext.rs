@@ -0,0 +1,104 @@
+//! Extensions for writing into `MaybeUninit` slots.
+
+use alloc::boxed::Box;
+use core::mem::{self, MaybeUninit};
+
+/// Writes an `Option<&T>` while checking that `None` is all zero bits.
+///
+/// Code zeroing memory to get `None` links, as [`Astruct::boxed_zeroed`]
+/// does, relies on this. Rust guarantees it for `Option<&T>` through the
+/// null niche, the debug assertion documents and checks that assumption
+/// where the value is written.
+///
+/// [`Astruct::boxed_zeroed`]: crate::Astruct::boxed_zeroed
+pub trait CheckedWrite<'a, T> {
+    /// Write `val` and return a reference to the now initialized slot.
+    fn checked_write_option_ref(&mut self, val: Option<&'a T>) -> &mut Option<&'a T>;
+}
+
+impl<'a, T> CheckedWrite<'a, T> for MaybeUninit<Option<&'a T>> {
+    #[inline]
+    fn checked_write_option_ref(&mut self, val: Option<&'a T>) -> &mut Option<&'a T> {
+        // Safety: Option<&T> and usize have the same size for sized T.
+        debug_assert_eq!(
+            unsafe { mem::transmute::<Option<&'a T>, usize>(None) },
+            0,
+            "None is not represented as a null pointer"
+        );
+        self.write(val)
+    }
+}
+
+/// `assume_init` that only commits once `check` accepts the value,
+/// otherwise the box is handed back still uninitialized.
+///
+/// `check` is for invariants beyond validity, such as a self pointer
+/// targeting the right field, e.g. `|a| a.validate().is_ok()`.
+///
+/// # Safety
+///
+/// Every field of the value must already be valid for its type, as for
+/// `assume_init`: `check` gets a `&T` to it. The value is not dropped on
+/// rejection, whatever it owns is leaked.
+pub unsafe fn try_assume_init<T>(
+    b: Box<MaybeUninit<T>>,
+    check: impl FnOnce(&T) -> bool,
+) -> Result<Box<T>, Box<MaybeUninit<T>>> {
+    if check(b.assume_init_ref()) {
+        Ok(b.assume_init())
+    } else {
+        Err(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repr_c_ordered_build;
+    use crate::selfref::Astruct;
+    use core::ptr::{self, addr_of_mut, NonNull};
+
+    // Plain reads and writes only, so `cargo +nightly miri test` can check
+    // it for UB.
+    #[test]
+    fn writes_some_and_none() {
+        let v = 5u32;
+        let mut slot = MaybeUninit::<Option<&u32>>::uninit();
+        let written = slot.checked_write_option_ref(Some(&v));
+        assert!(ptr::eq(written.unwrap(), &v));
+        let written = slot.checked_write_option_ref(None);
+        assert_eq!(*written, None);
+        // Safety: just written
+        assert_eq!(unsafe { slot.assume_init() }, None);
+    }
+
+    #[test]
+    fn try_assume_init_rejects_a_wrong_link() {
+        let mut b = Box::<Astruct>::new_uninit();
+        let p = b.as_mut_ptr();
+        let elsewhere = Box::new(0u32);
+        unsafe {
+            repr_c_ordered_build!(p => Astruct { a_u8: 1, a_u32: 2 });
+            // Deliberately not at (*p).a_u32
+            let link = NonNull::from(&*elsewhere);
+            addr_of_mut!((*p).op_a_u32).write(Some(link));
+        }
+        let mut b = unsafe { try_assume_init(b, |a| a.validate().is_ok()) }.unwrap_err();
+
+        // Fix the write and try again with the same allocation
+        let p = b.as_mut_ptr();
+        unsafe {
+            let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+            addr_of_mut!((*p).op_a_u32).write(Some(link));
+        }
+        let a = unsafe { try_assume_init(b, |a| a.validate().is_ok()) }.unwrap();
+        assert_eq!(a.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn zeroed_is_none() {
+        let zeroed = MaybeUninit::<Option<&u64>>::zeroed();
+        // Safety: all zero bits are None thanks to the null niche
+        assert_eq!(unsafe { zeroed.assume_init() }, None);
+    }
+}
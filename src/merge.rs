@@ -0,0 +1,140 @@
+//! Combining partially filled [`AstructBuilder`]s.
+//!
+//! Useful when values come from several sources, e.g. defaults overlaid
+//! with overrides. Builders never carry `op_a_u32`, the link is always
+//! computed by `build()` from the merged `a_u32`, so a pointer from either
+//! source can't leak into the result.
+
+use core::marker::PhantomData;
+
+use crate::builder::{AstructBuilder, NeedsU32, Unlinked};
+
+/// The typestate of a merged builder: `Unlinked` if either side is.
+pub trait MergeState<O> {
+    type Merged;
+}
+
+impl MergeState<NeedsU32> for NeedsU32 {
+    type Merged = NeedsU32;
+}
+
+impl MergeState<Unlinked> for NeedsU32 {
+    type Merged = Unlinked;
+}
+
+impl<O> MergeState<O> for Unlinked {
+    type Merged = Unlinked;
+}
+
+impl<S, T, P> AstructBuilder<S, T, P> {
+    /// Combine with `other`, keeping the receiver's value for every field
+    /// set on both.
+    ///
+    /// `a_u8` is left out if either side left it out with
+    /// [`include_a_u8_if`](AstructBuilder::include_a_u8_if).
+    pub fn merge<O>(self, other: AstructBuilder<O, T>) -> AstructBuilder<S::Merged, T, P>
+    where
+        S: MergeState<O>,
+    {
+        AstructBuilder {
+            a_u8: self.a_u8.or(other.a_u8),
+            a_u32: self.a_u32.or(other.a_u32),
+            skip_a_u8: self.skip_a_u8 || other.skip_a_u8,
+            parent: self.parent,
+            _state: PhantomData,
+        }
+    }
+
+    /// Combine with `other`, keeping `other`'s value for every field set
+    /// on both.
+    pub fn merge_prefer_other<O>(
+        self,
+        other: AstructBuilder<O, T>,
+    ) -> AstructBuilder<O::Merged, T, P>
+    where
+        O: MergeState<S>,
+    {
+        AstructBuilder {
+            a_u8: other.a_u8.or(self.a_u8),
+            a_u32: other.a_u32.or(self.a_u32),
+            skip_a_u8: self.skip_a_u8 || other.skip_a_u8,
+            parent: self.parent,
+            _state: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    // Raw field values, the state is irrelevant as these are never built
+    fn builder(a_u8: Option<u8>, a_u32: Option<u32>) -> AstructBuilder<NeedsU32> {
+        AstructBuilder {
+            a_u8,
+            a_u32,
+            skip_a_u8: false,
+            parent: (),
+            _state: PhantomData,
+        }
+    }
+
+    #[test]
+    fn every_combination_per_field() {
+        for l8 in [None, Some(1u8)] {
+            for r8 in [None, Some(2u8)] {
+                for l32 in [None, Some(1u32)] {
+                    for r32 in [None, Some(2u32)] {
+                        let m = builder(l8, l32).merge(builder(r8, r32));
+                        assert_eq!(m.a_u8, l8.or(r8));
+                        assert_eq!(m.a_u32, l32.or(r32));
+
+                        let m = builder(l8, l32).merge_prefer_other(builder(r8, r32));
+                        assert_eq!(m.a_u8, r8.or(l8));
+                        assert_eq!(m.a_u32, r32.or(l32));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn receiver_wins() {
+        let defaults = AstructBuilder::new().set_a_u8(1).set_a_u32(10);
+        let overrides = AstructBuilder::new().set_a_u32(20);
+        let a = overrides.merge(defaults).link().build();
+        assert_eq!((a.a_u8, a.a_u32), (1, 20));
+    }
+
+    #[test]
+    fn prefer_other_wins() {
+        let defaults = AstructBuilder::new().set_a_u8(1).set_a_u32(10);
+        let overrides = AstructBuilder::new().set_a_u8(2);
+        let a = defaults.merge_prefer_other(overrides).link().build();
+        assert_eq!((a.a_u8, a.a_u32), (2, 10));
+    }
+
+    #[test]
+    fn unset_a_u32_on_one_side_is_unlinked() {
+        let a = AstructBuilder::new()
+            .set_a_u8(3)
+            .merge(AstructBuilder::new().set_a_u32(30))
+            .link()
+            .build();
+        assert_eq!((a.a_u8, a.a_u32), (3, 30));
+    }
+
+    #[test]
+    fn merged_build_links_its_own_a_u32() {
+        let left = AstructBuilder::new().set_a_u32(1).link().build_linked();
+        let a = AstructBuilder::new()
+            .set_a_u32(left.a_u32)
+            .merge(AstructBuilder::new().set_a_u32(2))
+            .link()
+            .build();
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert!(!ptr::eq(a.link_ptr().unwrap().as_ptr(), &left.a_u32));
+        assert_eq!(a.referenced_u32(), Some(1));
+    }
+}
@@ -0,0 +1,164 @@
+//! Building values with several self references, each checked to point
+//! into the value's own allocation.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::{size_of, MaybeUninit};
+
+/// Like [`SelfRefBuilder`](crate::SelfRefBuilder) but `init` also records
+/// the target of every internal pointer it establishes, e.g. both links of
+/// a doubly linked node.
+///
+/// Before `assume_init()` each recorded pointer is checked to lie within
+/// `[base, base + size_of::<T>())`, so a link accidentally set to some
+/// external value, `op_a_u32 = Some(&other)`, is an error instead of a
+/// dangling pointer later.
+pub struct MultiSelfRef<T> {
+    uninit: Box<MaybeUninit<T>>,
+    refs: Vec<*const u8>,
+}
+
+impl<T> MultiSelfRef<T> {
+    pub fn new() -> Self {
+        MultiSelfRef {
+            uninit: Box::new_uninit(),
+            refs: Vec::new(),
+        }
+    }
+
+    /// Run `init` against the final heap address, passing it the list to
+    /// push each established self reference onto, then check them.
+    ///
+    /// On error the allocation is freed without dropping any of the
+    /// written fields.
+    ///
+    /// # Safety
+    ///
+    /// `init` must initialize every field of `T` and record every pointer
+    /// field it sets.
+    pub unsafe fn build(
+        mut self,
+        init: impl FnOnce(*mut T, &mut Vec<*const u8>),
+    ) -> Result<Box<T>, OutOfBoundsRef> {
+        let base = self.uninit.as_mut_ptr();
+        init(base, &mut self.refs);
+        let base = base as *const u8;
+        for &r in &self.refs {
+            let offset = (r.addr() as isize).wrapping_sub(base.addr() as isize);
+            if !(0..size_of::<T>() as isize).contains(&offset) {
+                return Err(OutOfBoundsRef { offset });
+            }
+        }
+        Ok(self.uninit.assume_init())
+    }
+}
+
+impl<T> Default for MultiSelfRef<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A recorded self reference pointed outside the allocation, `offset`
+/// bytes from its start.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfBoundsRef {
+    pub offset: isize,
+}
+
+impl fmt::Display for OutOfBoundsRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "self reference at offset {} is outside the value",
+            self.offset
+        )
+    }
+}
+
+impl core::error::Error for OutOfBoundsRef {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selfref::Astruct2;
+    use core::ptr::{self, addr_of_mut, NonNull};
+
+    #[repr(C)]
+    struct Node {
+        value: u32,
+        prev: *const u32,
+        next: *const u32,
+    }
+
+    #[test]
+    fn node_with_two_internal_links() {
+        let node = unsafe {
+            MultiSelfRef::<Node>::new().build(|p, refs| {
+                addr_of_mut!((*p).value).write(7);
+                let target = addr_of_mut!((*p).value) as *const u32;
+                addr_of_mut!((*p).prev).write(target);
+                addr_of_mut!((*p).next).write(target);
+                refs.push(target.cast());
+                refs.push(target.cast());
+            })
+        }
+        .unwrap();
+        assert!(ptr::eq(node.prev, &node.value));
+        assert!(ptr::eq(node.next, &node.value));
+    }
+
+    #[test]
+    fn astruct2_links_are_in_bounds() {
+        let a = unsafe {
+            MultiSelfRef::<Astruct2>::new().build(|p, refs| {
+                addr_of_mut!((*p).a_u8).write(1);
+                addr_of_mut!((*p).a_u32).write(2);
+                let a_u8 = addr_of_mut!((*p).a_u8);
+                let a_u32 = addr_of_mut!((*p).a_u32);
+                addr_of_mut!((*p).op_a_u8).write(NonNull::new(a_u8));
+                addr_of_mut!((*p).op_a_u32).write(NonNull::new(a_u32));
+                refs.push(a_u8.cast_const());
+                refs.push(a_u32.cast_const().cast());
+            })
+        }
+        .unwrap();
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn external_target_is_rejected() {
+        let external = 9u32;
+        let err = unsafe {
+            MultiSelfRef::<Node>::new().build(|p, refs| {
+                addr_of_mut!((*p).value).write(7);
+                let target = addr_of_mut!((*p).value) as *const u32;
+                addr_of_mut!((*p).prev).write(target);
+                addr_of_mut!((*p).next).write(&external);
+                refs.push(target.cast());
+                refs.push((&external as *const u32).cast());
+            })
+        }
+        .err()
+        .unwrap();
+        assert!(err.offset < 0 || err.offset >= size_of::<Node>() as isize);
+    }
+
+    #[test]
+    fn one_past_the_end_is_rejected() {
+        let err = unsafe {
+            MultiSelfRef::<u32>::new().build(|p, refs| {
+                p.write(1);
+                refs.push(p.add(1).cast_const().cast());
+            })
+        }
+        .err()
+        .unwrap();
+        assert_eq!(err, OutOfBoundsRef { offset: 4 });
+        assert_eq!(
+            err.to_string(),
+            "self reference at offset 4 is outside the value"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Building a struct whose field is built by its own builder.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::pin::Pin;
+
+use crate::builder::{AstructBuilder, Linked, NeedsU32};
+use crate::selfref::Astruct;
+use crate::Builder;
+
+/// A named linked [`Astruct`].
+///
+/// `child` is boxed and pinned like every built `Astruct`, see
+/// [`AstructBuilder::build`].
+#[derive(Debug, Builder)]
+pub struct ParentStruct {
+    pub child: Pin<Box<Astruct>>,
+    pub name: String,
+}
+
+/// The [`ParentStructBuilder`] an `AstructBuilder` from
+/// [`begin_child`](ParentStructBuilder::begin_child) finishes into.
+pub struct ParentBuildContext(ParentStructBuilder);
+
+impl ParentStructBuilder {
+    /// Build `child` inline instead of building an `Astruct` separately
+    /// and passing it to `set_child`.
+    ///
+    /// ```
+    /// use expr_builder_pattern::nested_builder::ParentStructBuilder;
+    ///
+    /// let p = ParentStructBuilder::new()
+    ///     .set_name("p".to_string())
+    ///     .begin_child()
+    ///     .set_a_u32(2)
+    ///     .link()
+    ///     .finish_child()
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(p.child.referenced_u32(), Some(2));
+    /// ```
+    pub fn begin_child(self) -> AstructBuilder<NeedsU32, u32, ParentBuildContext> {
+        AstructBuilder::with_parent(ParentBuildContext(self))
+    }
+}
+
+impl AstructBuilder<Linked, u32, ParentBuildContext> {
+    /// Build the child and store it in the parent builder.
+    pub fn finish_child(self) -> ParentStructBuilder {
+        let (ParentBuildContext(parent), child) = self.build_for_parent();
+        parent.set_child(child)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::BuildError;
+    use alloc::string::ToString;
+    use core::ptr;
+
+    #[test]
+    fn child_roundtrips_through_the_parent() {
+        let p = ParentStructBuilder::new()
+            .begin_child()
+            .set_a_u8(1)
+            .set_a_u32(42)
+            .link()
+            .finish_child()
+            .set_name("parent".to_string())
+            .build()
+            .unwrap();
+        assert_eq!(p.name, "parent");
+        assert_eq!((p.child.a_u8, p.child.a_u32), (1, 42));
+        assert!(ptr::eq(
+            p.child.link_ptr().unwrap().as_ptr(),
+            &p.child.a_u32
+        ));
+    }
+
+    #[test]
+    fn matches_building_the_child_separately() {
+        let separate = ParentStructBuilder::new()
+            .set_child(
+                AstructBuilder::new()
+                    .set_a_u8(3)
+                    .set_a_u32(4)
+                    .link()
+                    .build(),
+            )
+            .set_name("s".to_string())
+            .build()
+            .unwrap();
+        let nested = ParentStructBuilder::new()
+            .set_name("s".to_string())
+            .begin_child()
+            .set_a_u8(3)
+            .set_a_u32(4)
+            .link()
+            .finish_child()
+            .build()
+            .unwrap();
+        assert_eq!(separate.child.to_record(), nested.child.to_record());
+        assert_eq!(separate.name, nested.name);
+    }
+
+    #[test]
+    fn missing_child_is_reported() {
+        let err = ParentStructBuilder::new()
+            .set_name("p".to_string())
+            .build()
+            .unwrap_err();
+        assert_eq!(err, BuildError::MissingField { name: "child" });
+    }
+}
@@ -0,0 +1,124 @@
+//! Building a linked `Astruct` into caller provided memory.
+
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::{addr_of_mut, NonNull};
+
+use crate::builder::{NeedsU32, Unlinked};
+use crate::selfref::Astruct;
+
+/// Writes an [`Astruct`] field by field into a `&'buf mut
+/// MaybeUninit<Astruct>`, for targets where `Box::new_uninit` isn't
+/// available.
+///
+/// Each setter writes straight into the buffer, and like
+/// [`AstructBuilder`](crate::AstructBuilder) `place()` only exists once
+/// `a_u32` is written:
+///
+/// ```
+/// use core::mem::MaybeUninit;
+/// use expr_builder_pattern::no_alloc_builder::NoAllocAstructBuilder;
+///
+/// let mut buf = MaybeUninit::uninit();
+/// let a = NoAllocAstructBuilder::new(&mut buf).set_a_u8(1).set_a_u32(2).place();
+/// assert_eq!(a.referenced_u32(), Some(2));
+/// ```
+///
+/// The result is a plain `&'buf mut`: the buffer belongs to the caller,
+/// who can move it once the borrow ends, so it can't be pinned. Moving
+/// the value out leaves it unlinked as with [`Astruct::new`], which
+/// [`Astruct::validate`] reports.
+pub struct NoAllocAstructBuilder<'buf, S = NeedsU32> {
+    slot: &'buf mut MaybeUninit<Astruct>,
+    a_u8_set: bool,
+    _state: PhantomData<S>,
+}
+
+impl<'buf> NoAllocAstructBuilder<'buf> {
+    pub fn new(slot: &'buf mut MaybeUninit<Astruct>) -> Self {
+        NoAllocAstructBuilder {
+            slot,
+            a_u8_set: false,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<'buf, S> NoAllocAstructBuilder<'buf, S> {
+    pub fn set_a_u8(mut self, a_u8: u8) -> Self {
+        // Safety: writes one field of the slot without reading it
+        unsafe { addr_of_mut!((*self.slot.as_mut_ptr()).a_u8).write(a_u8) };
+        self.a_u8_set = true;
+        self
+    }
+
+    pub fn set_a_u32(self, a_u32: u32) -> NoAllocAstructBuilder<'buf, Unlinked> {
+        // Safety: as for set_a_u8
+        unsafe { addr_of_mut!((*self.slot.as_mut_ptr()).a_u32).write(a_u32) };
+        NoAllocAstructBuilder {
+            slot: self.slot,
+            a_u8_set: self.a_u8_set,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<'buf> NoAllocAstructBuilder<'buf, Unlinked> {
+    /// Link `op_a_u32` to the buffer's `a_u32` and hand out the now
+    /// initialized value. `a_u8` defaults to 0.
+    pub fn place(self) -> &'buf mut Astruct {
+        let p = self.slot.as_mut_ptr();
+        // Safety: a_u32 was written by set_a_u32, a_u8 here or by
+        // set_a_u8, the link last and the marker is a ZST. Every field is
+        // initialized before assume_init_mut.
+        unsafe {
+            if !self.a_u8_set {
+                addr_of_mut!((*p).a_u8).write(0);
+            }
+            let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+            addr_of_mut!((*p).op_a_u32).write(Some(link));
+            self.slot.assume_init_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn place_links_into_the_buffer() {
+        let mut buf = MaybeUninit::uninit();
+        let buf_addr = buf.as_ptr();
+        let a = NoAllocAstructBuilder::new(&mut buf)
+            .set_a_u32(7)
+            .set_a_u8(3)
+            .place();
+        assert!(ptr::eq(a, buf_addr));
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!((a.a_u8, a.referenced_u32()), (3, Some(7)));
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn a_u8_defaults_to_zero_and_setters_overwrite() {
+        let mut buf = MaybeUninit::uninit();
+        let a = NoAllocAstructBuilder::new(&mut buf)
+            .set_a_u32(1)
+            .set_a_u32(2)
+            .place();
+        assert_eq!((a.a_u8, a.referenced_u32()), (0, Some(2)));
+    }
+
+    #[test]
+    fn buffer_can_be_reused() {
+        let mut buf = MaybeUninit::uninit();
+        NoAllocAstructBuilder::new(&mut buf)
+            .set_a_u8(1)
+            .set_a_u32(1)
+            .place();
+        let a = NoAllocAstructBuilder::new(&mut buf).set_a_u32(5).place();
+        assert_eq!((a.a_u8, a.referenced_u32()), (0, Some(5)));
+    }
+}
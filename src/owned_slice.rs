@@ -0,0 +1,185 @@
+//! An owned `String` together with a `&str` token borrowed from it.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt;
+use core::ops::Range;
+use core::pin::Pin;
+use core::ptr::{addr_of, addr_of_mut, NonNull};
+use core::{slice, str};
+
+use crate::self_ref::SelfRefBuilder;
+
+/// Owns `text` and borrows the `token` sub slice of it, e.g. a parsed
+/// token kept next to its source.
+///
+/// The token is stored as a pointer and length into the `String`'s buffer
+/// and only handed out as `&str` borrowing `self`. `text` is never exposed
+/// mutably, so the buffer can't be reallocated under the token.
+pub struct OwnedSlice {
+    text: String,
+    token: NonNull<u8>,
+    token_len: usize,
+}
+
+// Safety: token points into the String owned alongside it, so this is
+// as thread safe as the String.
+unsafe impl Send for OwnedSlice {}
+unsafe impl Sync for OwnedSlice {}
+
+impl OwnedSlice {
+    /// Box `text` with the token set to `text[range]`.
+    ///
+    /// Like `SelfRefBuilder` the token is written after `text` inside the
+    /// `Box::new_uninit` allocation, then the box is pinned.
+    pub fn new(text: String, range: Range<usize>) -> Result<Pin<Box<Self>>, RangeError> {
+        check_range(&text, &range)?;
+        let s = unsafe {
+            SelfRefBuilder::<OwnedSlice>::new().build(
+                |p| addr_of_mut!((*p).text).write(text),
+                |p| {
+                    // text is initialized by now so reading it is fine
+                    let buf = (*addr_of!((*p).text)).as_ptr().cast_mut();
+                    let token = NonNull::new_unchecked(buf.add(range.start));
+                    addr_of_mut!((*p).token).write(token);
+                    addr_of_mut!((*p).token_len).write(range.len());
+                },
+            )
+        };
+        Ok(Box::into_pin(s))
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The sub slice of [`text`](OwnedSlice::text) given to `new`.
+    pub fn token(&self) -> &str {
+        // Safety: points at token_len bytes inside text, which is never
+        // mutated, starting and ending on char boundaries.
+        unsafe {
+            str::from_utf8_unchecked(slice::from_raw_parts(self.token.as_ptr(), self.token_len))
+        }
+    }
+}
+
+impl fmt::Debug for OwnedSlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedSlice")
+            .field("text", &self.text)
+            .field("token", &self.token())
+            .finish()
+    }
+}
+
+fn check_range(text: &str, range: &Range<usize>) -> Result<(), RangeError> {
+    if range.start > range.end {
+        return Err(RangeError::Reversed {
+            start: range.start,
+            end: range.end,
+        });
+    }
+    if range.end > text.len() {
+        return Err(RangeError::OutOfBounds {
+            end: range.end,
+            len: text.len(),
+        });
+    }
+    for index in [range.start, range.end] {
+        if !text.is_char_boundary(index) {
+            return Err(RangeError::NotCharBoundary { index });
+        }
+    }
+    Ok(())
+}
+
+/// Why a range can't be used as a token of the text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeError {
+    /// `start` is after `end`.
+    Reversed { start: usize, end: usize },
+    /// `end` is past the `len` bytes of the text.
+    OutOfBounds { end: usize, len: usize },
+    /// `index` falls inside a multi byte character.
+    NotCharBoundary { index: usize },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Reversed { start, end } => {
+                write!(f, "range start {} is after end {}", start, end)
+            }
+            RangeError::OutOfBounds { end, len } => {
+                write!(f, "range end {} is past the text length {}", end, len)
+            }
+            RangeError::NotCharBoundary { index } => {
+                write!(f, "byte {} is not on a char boundary", index)
+            }
+        }
+    }
+}
+
+impl core::error::Error for RangeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn token_is_the_sub_slice() {
+        let s = OwnedSlice::new("let x = 1;".to_string(), 4..5).unwrap();
+        assert_eq!(s.text(), "let x = 1;");
+        assert_eq!(s.token(), "x");
+        assert!(core::ptr::eq(s.token().as_ptr(), &s.text().as_bytes()[4]));
+    }
+
+    #[test]
+    fn unicode_boundaries() {
+        // 'é' is 2 bytes, '€' is 3
+        let text = "é€x".to_string();
+        let s = OwnedSlice::new(text.clone(), 2..5).unwrap();
+        assert_eq!(s.token(), "€");
+        assert_eq!(
+            OwnedSlice::new(text.clone(), 1..5).unwrap_err(),
+            RangeError::NotCharBoundary { index: 1 }
+        );
+        assert_eq!(
+            OwnedSlice::new(text, 2..4).unwrap_err(),
+            RangeError::NotCharBoundary { index: 4 }
+        );
+    }
+
+    #[test]
+    fn empty_ranges() {
+        let s = OwnedSlice::new("abc".to_string(), 3..3).unwrap();
+        assert_eq!(s.token(), "");
+        let s = OwnedSlice::new(String::new(), 0..0).unwrap();
+        assert_eq!((s.text(), s.token()), ("", ""));
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        #[allow(clippy::reversed_empty_ranges)]
+        let err = OwnedSlice::new("abc".to_string(), 2..1).unwrap_err();
+        assert_eq!(err, RangeError::Reversed { start: 2, end: 1 });
+        let err = OwnedSlice::new("abc".to_string(), 1..4).unwrap_err();
+        assert_eq!(err, RangeError::OutOfBounds { end: 4, len: 3 });
+        assert_eq!(err.to_string(), "range end 4 is past the text length 3");
+    }
+
+    #[test]
+    fn token_survives_moving_the_box() {
+        let s = OwnedSlice::new("hello world".to_string(), 6..11).unwrap();
+        let mut v = vec![s];
+        for i in 0..16 {
+            v.push(OwnedSlice::new(i.to_string(), 0..1).unwrap());
+        }
+        let s = v.swap_remove(0);
+        assert_eq!(s.token(), "world");
+        let boxed = Box::new(s);
+        assert_eq!(boxed.token(), "world");
+    }
+}
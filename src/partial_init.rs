@@ -0,0 +1,156 @@
+//! Runtime tracking of which fields of a `Box<MaybeUninit<T>>` were written.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::MaybeUninit;
+
+/// A `Box<MaybeUninit<T>>` plus a bitmask of the fields written so far.
+///
+/// Bit `i` corresponds to field `i` of `T` in declaration order. Adding a
+/// field to `T` without updating the initialization code then shows up as
+/// an error from [`try_assume_init`](PartialInit::try_assume_init) instead
+/// of silently reading uninitialized memory.
+pub struct PartialInit<T> {
+    uninit: Box<MaybeUninit<T>>,
+    initialized: u64,
+    field_count: usize,
+}
+
+impl<T> PartialInit<T> {
+    /// Allocate storage for a `T` with `field_count` fields to track.
+    ///
+    /// # Panics
+    ///
+    /// If `field_count` is larger than 64.
+    pub fn new(field_count: usize) -> Self {
+        assert!(field_count <= 64, "PartialInit tracks at most 64 fields");
+        PartialInit {
+            uninit: Box::new_uninit(),
+            initialized: 0,
+            field_count,
+        }
+    }
+
+    /// Pointer to the final location of the value, valid for writes.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.uninit.as_mut_ptr()
+    }
+
+    /// Record that field `field_index` has been written.
+    ///
+    /// # Safety
+    ///
+    /// The field must actually have been initialized through
+    /// [`as_mut_ptr`](PartialInit::as_mut_ptr).
+    ///
+    /// # Panics
+    ///
+    /// If `field_index` is not below the `field_count` given to `new`.
+    pub unsafe fn mark_initialized(&mut self, field_index: usize) {
+        assert!(
+            field_index < self.field_count,
+            "field_index {} out of range for {} fields",
+            field_index,
+            self.field_count
+        );
+        self.initialized |= 1 << field_index;
+    }
+
+    /// True once every field has been marked initialized.
+    pub fn is_complete(&self) -> bool {
+        self.missing().is_empty()
+    }
+
+    /// Indices of the fields not yet marked initialized.
+    pub fn missing(&self) -> Vec<usize> {
+        (0..self.field_count)
+            .filter(|i| self.initialized & (1 << i) == 0)
+            .collect()
+    }
+
+    /// Return the initialized box, or the indices of the fields that were
+    /// never written. On error the allocation is freed without dropping
+    /// any of the written fields.
+    pub fn try_assume_init(self) -> Result<Box<T>, PartialInitError> {
+        let missing = self.missing();
+        if missing.is_empty() {
+            // Safety: every field has been marked initialized.
+            Ok(unsafe { self.uninit.assume_init() })
+        } else {
+            Err(PartialInitError { missing })
+        }
+    }
+}
+
+/// Returned by [`PartialInit::try_assume_init`] when fields are missing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialInitError {
+    pub missing: Vec<usize>,
+}
+
+impl fmt::Display for PartialInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "uninitialized fields: {:?}", self.missing)
+    }
+}
+
+impl core::error::Error for PartialInitError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selfref::Astruct;
+    use core::ptr::{self, addr_of_mut, NonNull};
+
+    #[test]
+    fn complete_astruct_assumes_init() {
+        let mut pi = PartialInit::<Astruct>::new(4);
+        let p = pi.as_mut_ptr();
+        unsafe {
+            addr_of_mut!((*p).a_u8).write(1);
+            pi.mark_initialized(0);
+            addr_of_mut!((*p).a_u32).write(2);
+            pi.mark_initialized(1);
+            addr_of_mut!((*p).op_a_u32)
+                .write(Some(NonNull::new_unchecked(addr_of_mut!((*p).a_u32))));
+            pi.mark_initialized(2);
+            // _pin is a zero sized marker
+            pi.mark_initialized(3);
+        }
+        assert!(pi.is_complete());
+        let a = pi.try_assume_init().unwrap();
+        assert_eq!(a.a_u8, 1);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn missing_fields_are_reported() {
+        let mut pi = PartialInit::<Astruct>::new(4);
+        let p = pi.as_mut_ptr();
+        unsafe {
+            addr_of_mut!((*p).a_u8).write(1);
+            pi.mark_initialized(0);
+            pi.mark_initialized(3);
+        }
+        assert!(!pi.is_complete());
+        assert_eq!(pi.missing(), [1, 2]);
+        let err = pi.try_assume_init().err().unwrap();
+        assert_eq!(err.missing, [1, 2]);
+        assert_eq!(err.to_string(), "uninitialized fields: [1, 2]");
+    }
+
+    #[test]
+    fn zero_fields_is_complete() {
+        let pi = PartialInit::<()>::new(0);
+        assert!(pi.is_complete());
+        assert!(pi.try_assume_init().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn mark_out_of_range_panics() {
+        let mut pi = PartialInit::<u32>::new(1);
+        unsafe { pi.mark_initialized(1) };
+    }
+}
@@ -0,0 +1,112 @@
+//! Drop logic that receives the value pinned.
+
+use core::ops::Deref;
+use core::pin::Pin;
+
+/// Cleanup for a type whose address must not change until it is gone.
+///
+/// `Drop::drop` gets `&mut Self`, which would allow moving out of a value
+/// that was promised to stay put. `pin_drop` gets `Pin<&mut Self>`
+/// instead, so for a `!Unpin` type it can't be moved from safe code:
+///
+/// ```compile_fail
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use expr_builder_pattern::pin_drop::PinDrop;
+///
+/// struct Pinned {
+///     v: Vec<u32>,
+///     _pin: PhantomPinned,
+/// }
+///
+/// impl PinDrop for Pinned {
+///     fn pin_drop(self: Pin<&mut Self>) {
+///         // get_mut requires Unpin
+///         let v = core::mem::take(&mut self.get_mut().v);
+///         drop(v);
+///     }
+/// }
+/// ```
+pub trait PinDrop {
+    fn pin_drop(self: Pin<&mut Self>);
+}
+
+/// Owns a `T` and runs [`PinDrop::pin_drop`] when dropped.
+///
+/// The wrapper pins `T` structurally: it never hands out `&mut T`, only
+/// [`as_pin_mut`](PinDropWrapper::as_pin_mut) from a pinned wrapper and
+/// `&T` through `Deref`. Once the wrapper itself is pinned, `T` therefore
+/// can't move, and the wrapper's own `Drop` is the last access to it. That
+/// is what makes creating the `Pin<&mut T>` in `Drop` sound: the `Pin`
+/// contract only requires the memory not to be moved or reused before
+/// drop runs, and nothing uses `T` after `pin_drop` returns apart from
+/// its regular drop glue, which runs in place.
+pub struct PinDropWrapper<T: PinDrop>(T);
+
+impl<T: PinDrop> PinDropWrapper<T> {
+    pub fn new(value: T) -> Self {
+        PinDropWrapper(value)
+    }
+
+    /// Pinned access to the wrapped value.
+    pub fn as_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        // Safety: structural pinning, `T` is never moved out of the wrapper.
+        unsafe { self.map_unchecked_mut(|w| &mut w.0) }
+    }
+}
+
+impl<T: PinDrop> Deref for PinDropWrapper<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: PinDrop> Drop for PinDropWrapper<T> {
+    fn drop(&mut self) {
+        // Safety: see the type level docs, `self.0` is not moved before,
+        // during or after this call.
+        unsafe { Pin::new_unchecked(&mut self.0) }.pin_drop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::cell::Cell;
+    use core::marker::PhantomPinned;
+
+    struct Tracked<'c> {
+        // Address of the value when it was pinned and when pin_drop ran
+        seen: &'c Cell<[usize; 2]>,
+        drops: &'c Cell<u32>,
+        _pin: PhantomPinned,
+    }
+
+    impl PinDrop for Tracked<'_> {
+        fn pin_drop(self: Pin<&mut Self>) {
+            let seen = self.seen.get();
+            self.seen.set([seen[0], &*self as *const Self as usize]);
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    #[test]
+    fn pin_drop_runs_once_in_place() {
+        let seen = Cell::new([0, 0]);
+        let drops = Cell::new(0);
+        let mut w = Box::pin(PinDropWrapper::new(Tracked {
+            seen: &seen,
+            drops: &drops,
+            _pin: PhantomPinned,
+        }));
+        let addr = &*w.as_mut().as_pin_mut() as *const Tracked as usize;
+        seen.set([addr, 0]);
+        drop(w);
+        assert_eq!(drops.get(), 1);
+        let [pinned_at, dropped_at] = seen.get();
+        assert_eq!(pinned_at, dropped_at);
+    }
+}
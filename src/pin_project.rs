@@ -0,0 +1,139 @@
+//! Safe field access through `Pin<&mut Self>` for self-referential structs.
+
+use core::marker::PhantomData;
+
+/// Declare a struct together with a projection of `Pin<&mut Self>` onto
+/// its fields.
+///
+/// Fields marked `#[pin]` are structurally pinned and project to
+/// `Pin<&mut Field>`, all others to `&mut Field`. If any field is pinned
+/// the struct is `!Unpin`, and implementing `Drop` for it is a compile
+/// error since a `drop(&mut self)` could move pinned fields. The
+/// projection type is named with `#[project = Name]`, which must come
+/// first. Only lifetime parameters are supported and fields can't carry
+/// other attributes.
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use expr_builder_pattern::pin_project;
+///
+/// pin_project! {
+///     #[project = PairProjection]
+///     pub struct Pair {
+///         #[pin]
+///         pinned: PhantomPinned,
+///         pub count: u32,
+///     }
+/// }
+///
+/// let mut pair = Box::pin(Pair { pinned: PhantomPinned, count: 1 });
+/// let proj = pair.as_mut().project();
+/// *proj.count += 1;
+/// let _: Pin<&mut PhantomPinned> = proj.pinned;
+/// assert_eq!(pair.count, 2);
+/// ```
+///
+/// A struct with a `#[pin]` field is `!Unpin` whatever the field type:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::pin_project;
+///
+/// pin_project! {
+///     #[project = WrapProjection]
+///     struct Wrap {
+///         #[pin]
+///         value: u32,
+///     }
+/// }
+///
+/// fn unpin<T: Unpin>() {}
+/// unpin::<Wrap>();
+/// ```
+///
+/// Neither can it implement `Drop`:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::pin_project;
+///
+/// pin_project! {
+///     #[project = WrapProjection]
+///     struct Wrap {
+///         #[pin]
+///         value: u32,
+///     }
+/// }
+///
+/// impl Drop for Wrap {
+///     fn drop(&mut self) {}
+/// }
+/// ```
+#[macro_export]
+macro_rules! pin_project {
+    (@ty pin, $lt:lifetime, $ty:ty) => { ::core::pin::Pin<&$lt mut $ty> };
+    (@ty , $lt:lifetime, $ty:ty) => { &$lt mut $ty };
+    (@expr pin, $field:ident) => { ::core::pin::Pin::new_unchecked($field) };
+    (@expr , $field:ident) => { $field };
+    (@marker pin) => { ::core::marker::PhantomPinned };
+    (@marker) => { () };
+    (
+        #[project = $proj:ident]
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident $(<$($lt:lifetime),+ $(,)?>)? {
+            $($(#[$pin:ident])? $fvis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name $(<$($lt),+>)? {
+            $($fvis $field: $ty),*
+        }
+
+        #[doc = concat!("Projection of `Pin<&mut ", stringify!($name), ">` onto its fields.")]
+        #[allow(dead_code)]
+        $vis struct $proj<'__pin $($(, $lt)+)?>
+        where
+            $name $(<$($lt),+>)?: '__pin,
+        {
+            $($fvis $field: $crate::pin_project!(@ty $($pin)?, '__pin, $ty)),*
+        }
+
+        impl $(<$($lt),+>)? $name $(<$($lt),+>)? {
+            /// Borrow every field, `#[pin]` ones as `Pin<&mut _>`.
+            #[allow(dead_code)]
+            $vis fn project<'__pin>(
+                self: ::core::pin::Pin<&'__pin mut Self>,
+            ) -> $proj<'__pin $($(, $lt)+)?> {
+                // Safety: `#[pin]` fields are only handed out pinned, the
+                // struct is `!Unpin` when there are any and can't
+                // implement `Drop`.
+                unsafe {
+                    let Self { $($field),* } = self.get_unchecked_mut();
+                    $proj { $($field: $crate::pin_project!(@expr $($pin)?, $field)),* }
+                }
+            }
+        }
+
+        // Unpin only when no field is `#[pin]`, the free lifetime keeps the
+        // bound from being rejected as trivially false.
+        impl<'__pin $($(, $lt)+)?> ::core::marker::Unpin for $name $(<$($lt),+>)?
+        where
+            $crate::pin_project::__Origin<
+                '__pin,
+                ($($crate::pin_project!(@marker $($pin)?),)*),
+            >: ::core::marker::Unpin,
+        {
+        }
+
+        // Conflicts with the blanket impl if the struct implements Drop
+        const _: () = {
+            #[allow(dead_code)]
+            trait MustNotImplDrop {}
+            #[allow(drop_bounds)]
+            impl<T: ::core::ops::Drop> MustNotImplDrop for T {}
+            impl $(<$($lt),+>)? MustNotImplDrop for $name $(<$($lt),+>)? {}
+        };
+    };
+}
+
+#[doc(hidden)]
+pub struct __Origin<'a, T>(PhantomData<&'a ()>, T);
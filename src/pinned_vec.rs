@@ -0,0 +1,88 @@
+//! A growable list of linked `Astruct`s.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::pin::Pin;
+
+use crate::selfref::Astruct;
+
+/// Linked [`Astruct`]s in a `Vec`, each in its own pinned box.
+///
+/// A `Vec<Astruct>` would move its elements whenever it reallocates and
+/// leave every `op_a_u32` dangling. Here reallocating only moves the boxes,
+/// the values stay put, and elements are only handed out pinned so they
+/// can't be moved out either.
+pub struct PinnedVec<T = u32> {
+    items: Vec<Pin<Box<Astruct<T>>>>,
+}
+
+impl<T> PinnedVec<T> {
+    pub fn new() -> Self {
+        PinnedVec { items: Vec::new() }
+    }
+
+    /// Append an `Astruct` linked to its own `a_u32`.
+    pub fn push_linked(&mut self, a_u8: u8, a_u32: T) -> Pin<&mut Astruct<T>> {
+        self.items.push(Astruct::boxed_linked(a_u8, a_u32));
+        self.items.last_mut().unwrap().as_mut()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Pin<&Astruct<T>>> {
+        self.items.get(index).map(Pin::as_ref)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Pin<&Astruct<T>>> {
+        self.items.iter().map(Pin::as_ref)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<T> Default for PinnedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn links_survive_reallocation() {
+        let mut v = PinnedVec::new();
+        let first = &*v.push_linked(0, 0u32) as *const Astruct;
+        let mut reallocs = 0;
+        for i in 1..1000u32 {
+            let cap = v.items.capacity();
+            v.push_linked(i as u8, i);
+            if v.items.capacity() != cap {
+                reallocs += 1;
+            }
+        }
+        assert!(reallocs >= 3);
+        assert_eq!(v.len(), 1000);
+        assert!(ptr::eq(&*v.get(0).unwrap(), first));
+        for (i, a) in v.iter().enumerate() {
+            assert_eq!(a.validate(), Ok(()));
+            assert_eq!(a.referenced_u32(), Some(i as u32));
+        }
+    }
+
+    #[test]
+    fn get_out_of_bounds() {
+        let mut v = PinnedVec::<u32>::default();
+        assert!(v.is_empty());
+        assert!(v.get(0).is_none());
+        *v.push_linked(1, 2).a_u8_mut() = 3;
+        assert_eq!(v.get(0).unwrap().a_u8, 3);
+        assert!(v.get(1).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+//! Reusing the allocations of linked `Astruct`s.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::pin::Pin;
+
+use crate::in_place::InPlaceInit;
+use crate::selfref::Astruct;
+
+/// Keeps the boxes of released [`PoolGuard`]s for the next
+/// [`acquire`](AstructPool::acquire), so a hot loop building and dropping
+/// `Astruct`s doesn't go through the allocator every time.
+///
+/// At most `capacity` free boxes are kept, releasing a guard when that
+/// many are waiting frees its box instead.
+pub struct AstructPool<T = u32> {
+    free: RefCell<Vec<Box<MaybeUninit<Astruct<T>>>>>,
+    capacity: usize,
+}
+
+impl<T> AstructPool<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        AstructPool {
+            free: RefCell::new(Vec::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// A linked `Astruct` in a recycled box when one is free, else in a
+    /// new one.
+    ///
+    /// Either way the value is initialized in place with
+    /// [`InPlaceInit`], so `op_a_u32` points at the box it ends up in.
+    pub fn acquire(&self, a_u8: u8, a_u32: T) -> PoolGuard<'_, T> {
+        let mut slot = self.free.borrow_mut().pop().unwrap_or_else(Box::new_uninit);
+        // Safety: the slot is in its final allocation and unused
+        unsafe { Astruct::init_in_place(slot.as_mut_ptr(), (a_u8, a_u32)) };
+        PoolGuard {
+            slot: Some(slot),
+            pool: self,
+        }
+    }
+
+    /// Most free boxes kept for reuse.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Free boxes waiting for reuse.
+    pub fn free_len(&self) -> usize {
+        self.free.borrow().len()
+    }
+}
+
+/// A linked `Astruct` borrowed from an [`AstructPool`], dropped and its box
+/// handed back to the pool when the guard goes away.
+///
+/// Derefs to the `Astruct`, [`as_mut`](PoolGuard::as_mut) gives the
+/// `Pin<&mut Astruct>` for the pinned setters. Moving the guard only
+/// moves the box pointer, the value stays put.
+pub struct PoolGuard<'p, T = u32> {
+    // Initialized while the guard exists, None only inside drop
+    slot: Option<Box<MaybeUninit<Astruct<T>>>>,
+    pool: &'p AstructPool<T>,
+}
+
+impl<T> PoolGuard<'_, T> {
+    pub fn as_mut(&mut self) -> Pin<&mut Astruct<T>> {
+        let slot = self.slot.as_mut().unwrap();
+        // Safety: initialized by acquire and never moved out of the box
+        unsafe { Pin::new_unchecked(slot.assume_init_mut()) }
+    }
+
+    pub fn as_ref(&self) -> Pin<&Astruct<T>> {
+        // Safety: as for as_mut
+        unsafe { Pin::new_unchecked(self) }
+    }
+}
+
+impl<T> Deref for PoolGuard<'_, T> {
+    type Target = Astruct<T>;
+
+    fn deref(&self) -> &Astruct<T> {
+        // Safety: initialized by acquire
+        unsafe { self.slot.as_ref().unwrap().assume_init_ref() }
+    }
+}
+
+impl<T> Drop for PoolGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut slot = self.slot.take().unwrap();
+        // Safety: initialized and dropped only here, the box goes back as
+        // uninitialized memory
+        unsafe { slot.assume_init_drop() };
+        let mut free = self.pool.free.borrow_mut();
+        if free.len() < self.pool.capacity {
+            free.push(slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::ptr;
+
+    #[test]
+    fn released_boxes_are_reused() {
+        let pool = AstructPool::with_capacity(4);
+        let a = pool.acquire(1, 2u32);
+        let first = &*a as *const Astruct;
+        drop(a);
+        assert_eq!(pool.free_len(), 1);
+        for i in 0..100u32 {
+            let mut a = pool.acquire(i as u8, i);
+            assert!(ptr::eq(&*a, first));
+            assert_eq!(a.validate(), Ok(()));
+            assert_eq!((a.a_u8, a.referenced_u32()), (i as u8, Some(i)));
+            a.as_mut().set_a_u32(i + 1);
+            assert_eq!(a.as_ref().referenced_u32(), Some(i + 1));
+        }
+        assert_eq!(pool.free_len(), 1);
+    }
+
+    #[test]
+    fn free_list_is_bounded() {
+        let pool = AstructPool::with_capacity(2);
+        let held: Vec<_> = (0..5u32).map(|i| pool.acquire(0, i)).collect();
+        let addrs: Vec<_> = held.iter().map(|a| &**a as *const Astruct).collect();
+        assert_eq!(pool.free_len(), 0);
+        drop(held);
+        assert_eq!(pool.free_len(), pool.capacity());
+
+        // Last released first, the two kept boxes are from the first guards
+        let a = pool.acquire(0, 0);
+        let b = pool.acquire(0, 0);
+        assert!(ptr::eq(&*a, addrs[1]));
+        assert!(ptr::eq(&*b, addrs[0]));
+    }
+
+    #[test]
+    fn values_drop_on_release() {
+        let payload = Rc::new(());
+        let pool = AstructPool::with_capacity(1);
+        let a = pool.acquire(0, payload.clone());
+        let b = pool.acquire(0, payload.clone());
+        assert_eq!(Rc::strong_count(&payload), 3);
+        drop(a);
+        drop(b);
+        assert_eq!(Rc::strong_count(&payload), 1);
+        drop(pool);
+    }
+}
@@ -0,0 +1,196 @@
+//! A pointer stored as an offset from its own address.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::size_of;
+use core::ptr::NonNull;
+
+/// Points at a `T` through a signed byte offset from the `RelPtr` itself.
+///
+/// Unlike an absolute self pointer such as `Astruct::op_a_u32` it keeps
+/// pointing at the same field after the containing struct is copied
+/// bitwise, as long as both end up at the same relative position. An
+/// offset of 0 means null, a `RelPtr` can't point at itself.
+pub struct RelPtr<T> {
+    offset: isize,
+    _target: PhantomData<*const T>,
+}
+
+// Safety: a RelPtr only hands out the target the way `&T` would, so it
+// is as thread safe as `&T`, which is Send and Sync for `T: Sync`.
+unsafe impl<T: Sync> Send for RelPtr<T> {}
+unsafe impl<T: Sync> Sync for RelPtr<T> {}
+
+impl<T> RelPtr<T> {
+    pub const fn null() -> Self {
+        RelPtr {
+            offset: 0,
+            _target: PhantomData,
+        }
+    }
+
+    /// Point at `target`, or null when `target` is null.
+    pub fn set(&mut self, target: *const T) {
+        self.offset = if target.is_null() {
+            0
+        } else {
+            target.addr() as isize - self.addr() as isize
+        };
+    }
+
+    /// The target relative to where this `RelPtr` is now, for comparing
+    /// addresses.
+    ///
+    /// The pointer is derived from `self`, which only covers the
+    /// `RelPtr`'s own bytes, so it must not be dereferenced. Use
+    /// [`get_in`](RelPtr::get_in) to read the target.
+    pub fn get(&self) -> Option<NonNull<T>> {
+        if self.is_null() {
+            return None;
+        }
+        let p = (self as *const Self)
+            .cast::<u8>()
+            .wrapping_offset(self.offset);
+        NonNull::new(p.cast::<T>().cast_mut())
+    }
+
+    /// The target relative to where this `RelPtr` is now, derived from
+    /// `owner`, the value this `RelPtr` is a field of.
+    ///
+    /// The pointer has the provenance of the `&C`, so it may be read
+    /// while `owner` is borrowed if the target is a field of `owner`, but
+    /// not written.
+    ///
+    /// # Panics
+    ///
+    /// If this `RelPtr` doesn't lie inside `owner`.
+    pub fn get_in<C>(&self, owner: &C) -> Option<NonNull<T>> {
+        let base = (owner as *const C).cast::<u8>();
+        let field = self.addr().wrapping_sub(base.addr());
+        assert!(
+            size_of::<C>()
+                .checked_sub(size_of::<Self>())
+                .is_some_and(|last| field <= last),
+            "RelPtr::get_in: not a field of owner"
+        );
+        if self.is_null() {
+            return None;
+        }
+        let p = base.wrapping_add(field).wrapping_offset(self.offset);
+        NonNull::new(p.cast::<T>().cast_mut())
+    }
+
+    /// Bytes from this `RelPtr` to its target, 0 when null.
+    pub fn offset(&self) -> isize {
+        self.offset
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.offset == 0
+    }
+
+    fn addr(&self) -> usize {
+        (self as *const Self).addr()
+    }
+}
+
+impl<T> Clone for RelPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RelPtr<T> {}
+
+impl<T> Default for RelPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> fmt::Debug for RelPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelPtr")
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::ptr;
+
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    struct Rel {
+        a_u8: u8,
+        a_u32: u32,
+        op_a_u32: RelPtr<u32>,
+    }
+
+    fn linked(a_u32: u32) -> Box<Rel> {
+        let mut b = Box::new(Rel {
+            a_u8: 1,
+            a_u32,
+            op_a_u32: RelPtr::null(),
+        });
+        let target = &b.a_u32 as *const u32;
+        b.op_a_u32.set(target);
+        b
+    }
+
+    #[test]
+    fn null_by_default() {
+        let r = RelPtr::<u32>::default();
+        assert!(r.is_null());
+        assert_eq!(r.get(), None);
+    }
+
+    #[test]
+    fn points_at_target() {
+        let b = linked(7);
+        assert!(!b.op_a_u32.is_null());
+        assert!(ptr::eq(b.op_a_u32.get().unwrap().as_ptr(), &b.a_u32));
+        assert_eq!(unsafe { *b.op_a_u32.get_in(&*b).unwrap().as_ptr() }, 7);
+    }
+
+    #[test]
+    fn survives_copy_into_new_box() {
+        let original = linked(8);
+        let copy = Box::new(*original);
+        drop(original);
+        let p = copy.op_a_u32.get_in(&*copy).unwrap();
+        assert!(ptr::eq(p.as_ptr(), &copy.a_u32));
+        assert_eq!(unsafe { *p.as_ptr() }, 8);
+        assert_eq!(copy.a_u8, 1);
+    }
+
+    #[test]
+    fn survives_memcpy_into_uninit_box() {
+        let original = linked(9);
+        let mut uninit = Box::<Rel>::new_uninit();
+        let copy = unsafe {
+            ptr::copy_nonoverlapping(&*original, uninit.as_mut_ptr(), 1);
+            uninit.assume_init()
+        };
+        assert!(ptr::eq(copy.op_a_u32.get().unwrap().as_ptr(), &copy.a_u32));
+    }
+
+    #[test]
+    fn set_null_clears() {
+        let mut b = linked(1);
+        b.op_a_u32.set(ptr::null());
+        assert!(b.op_a_u32.is_null());
+        assert_eq!(b.op_a_u32.get_in(&*b), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a field of owner")]
+    fn get_in_rejects_a_foreign_owner() {
+        let b = linked(1);
+        let other = linked(2);
+        b.op_a_u32.get_in(&*other);
+    }
+}
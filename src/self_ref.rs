@@ -0,0 +1,259 @@
+//! Generic helpers for initializing self-referential values in place.
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+use core::ptr::NonNull;
+
+use crate::maybe_uninit_ext::try_assume_init;
+
+/// Wraps `Box::<T>::new_uninit()` and initializes it in two phases.
+///
+/// The first closure writes the value fields, the second the pointer
+/// fields. Links are therefore only computed once everything they may
+/// point at has been written, and `assume_init()` is called only after
+/// both closures have returned.
+pub struct SelfRefBuilder<T> {
+    uninit: Box<MaybeUninit<T>>,
+}
+
+impl<T> SelfRefBuilder<T> {
+    pub fn new() -> Self {
+        SelfRefBuilder {
+            uninit: Box::new_uninit(),
+        }
+    }
+
+    /// Run `values` then `links` against the final heap address and
+    /// return the initialized box.
+    ///
+    /// # Safety
+    ///
+    /// Together the two closures must initialize every field of `T`, and
+    /// `values` must not read through any pointer field since those are
+    /// still uninitialized when it runs.
+    pub unsafe fn build(
+        mut self,
+        values: impl FnOnce(*mut T),
+        links: impl FnOnce(*mut T),
+    ) -> Box<T> {
+        let p = self.uninit.as_mut_ptr();
+        values(p);
+        links(p);
+        self.uninit.assume_init()
+    }
+
+    /// [`build`](SelfRefBuilder::build) committing only if `check` accepts
+    /// the value, see [`try_assume_init`].
+    ///
+    /// # Safety
+    ///
+    /// As for `build`.
+    pub unsafe fn try_build(
+        mut self,
+        values: impl FnOnce(*mut T),
+        links: impl FnOnce(*mut T),
+        check: impl FnOnce(&T) -> bool,
+    ) -> Result<Box<T>, Box<MaybeUninit<T>>> {
+        let p = self.uninit.as_mut_ptr();
+        values(p);
+        links(p);
+        try_assume_init(self.uninit, check)
+    }
+}
+
+impl<T> Default for SelfRefBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Single-closure counterpart of [`SelfRefBuilder`] returning a pinned box.
+///
+/// ```
+/// use expr_builder_pattern::self_ref::SelfRef;
+///
+/// use std::ptr::{addr_of_mut, NonNull};
+///
+/// struct Pair {
+///     value: u32,
+///     link: NonNull<u32>,
+/// }
+///
+/// let x = unsafe {
+///     SelfRef::<Pair>::build(|p| {
+///         addr_of_mut!((*p).value).write(47);
+///         let link = NonNull::new_unchecked(addr_of_mut!((*p).value));
+///         addr_of_mut!((*p).link).write(link);
+///     })
+/// };
+/// assert!(std::ptr::eq(x.link.as_ptr(), &x.value));
+/// ```
+pub struct SelfRef<T>(PhantomData<T>);
+
+impl<T> SelfRef<T> {
+    /// Allocate an uninitialized `T`, let `init` write every field at its
+    /// final address, then pin the result.
+    ///
+    /// # Safety
+    ///
+    /// `init` must initialize every field of `T`.
+    pub unsafe fn build(init: impl FnOnce(*mut T)) -> Pin<Box<T>> {
+        Box::into_pin(SelfRefBuilder::new().build(init, |_| {}))
+    }
+}
+
+/// Field types that can hold a link to a `T` in the same allocation, used
+/// by `#[derive(Builder)]` for `self_ref` fields.
+///
+/// Only pointers implement it: the built box can still be moved out of, so
+/// reading the link is left to `unsafe` code that knows the value is still
+/// where it was built.
+///
+/// ```
+/// use core::ptr::NonNull;
+/// use expr_builder_pattern::Builder;
+///
+/// #[derive(Builder)]
+/// struct Linked {
+///     a_u32: u32,
+///     #[builder(self_ref(target = "a_u32"))]
+///     op_a_u32: Option<NonNull<u32>>,
+/// }
+///
+/// let a = LinkedBuilder::new().set_a_u32(5).build().unwrap();
+/// assert!(core::ptr::eq(a.op_a_u32.unwrap().as_ptr(), &a.a_u32));
+/// ```
+///
+/// A reference would let the caller pick its lifetime, so `derive(Builder)`
+/// rejects one for `self_ref` and `lazy_default` fields:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::Builder;
+///
+/// #[derive(Builder)]
+/// struct Linked<'m> {
+///     a_u32: u32,
+///     #[builder(self_ref(target = "a_u32"))]
+///     op_a_u32: Option<&'m u32>,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use expr_builder_pattern::Builder;
+///
+/// #[derive(Builder)]
+/// struct Linked<'m> {
+///     a_u32: u32,
+///     #[builder(lazy_default = "self_ref")]
+///     op_a_u32: Option<&'m u32>,
+/// }
+/// ```
+///
+/// Paths inside or around the `Option` don't get past the check:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::Builder;
+///
+/// #[derive(Builder)]
+/// struct Linked<'m> {
+///     a_u32: u32,
+///     #[builder(lazy_default = "self_ref")]
+///     op_a_u32: Option<&'m ::core::primitive::u32>,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use expr_builder_pattern::Builder;
+///
+/// #[derive(Builder)]
+/// struct Linked<'m> {
+///     a_u32: u32,
+///     #[builder(lazy_default = "self_ref")]
+///     op_a_u32: ::std::option::Option<&'m u32>,
+/// }
+/// ```
+pub trait SelfRefLink<T> {
+    /// Turn the address of the target field into the link.
+    ///
+    /// # Safety
+    ///
+    /// `target` must not be null.
+    unsafe fn link(target: *mut T) -> Self;
+}
+
+impl<T> SelfRefLink<T> for NonNull<T> {
+    unsafe fn link(target: *mut T) -> Self {
+        NonNull::new_unchecked(target)
+    }
+}
+
+impl<T> SelfRefLink<T> for Option<NonNull<T>> {
+    unsafe fn link(target: *mut T) -> Self {
+        NonNull::new(target)
+    }
+}
+
+impl<T> SelfRefLink<T> for *const T {
+    unsafe fn link(target: *mut T) -> Self {
+        target
+    }
+}
+
+impl<T> SelfRefLink<T> for *mut T {
+    unsafe fn link(target: *mut T) -> Self {
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_place::InPlaceInit;
+    use crate::selfref::{Astruct, Xstruct};
+    use core::ptr::{self, addr_of_mut, NonNull};
+
+    #[test]
+    fn self_ref_builds_astruct() {
+        let a = unsafe {
+            SelfRef::<Astruct>::build(|p| {
+                addr_of_mut!((*p).a_u8).write(1);
+                addr_of_mut!((*p).a_u32).write(123);
+                let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+                addr_of_mut!((*p).op_a_u32).write(Some(link));
+            })
+        };
+        assert_eq!(a.a_u8, 1);
+        assert_eq!(a.referenced_u32().unwrap(), 123);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+
+    #[test]
+    fn self_ref_builds_xstruct() {
+        let x = unsafe { SelfRef::<Xstruct>::build(|p| Xstruct::init_in_place(p, 47)) };
+        assert_eq!(*x.as_ref().p(), 47);
+        assert_eq!(x.validate(), Ok(()));
+    }
+
+    #[test]
+    fn self_ref_builder_runs_values_before_links() {
+        let order = core::cell::RefCell::new(Vec::new());
+        let a = unsafe {
+            SelfRefBuilder::<Astruct>::new().build(
+                |p| {
+                    order.borrow_mut().push("values");
+                    addr_of_mut!((*p).a_u8).write(1);
+                    addr_of_mut!((*p).a_u32).write(2);
+                },
+                |p| {
+                    order.borrow_mut().push("links");
+                    let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+                    addr_of_mut!((*p).op_a_u32).write(Some(link));
+                },
+            )
+        };
+        assert_eq!(*order.borrow(), ["values", "links"]);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    }
+}
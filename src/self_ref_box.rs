@@ -0,0 +1,152 @@
+//! A heap allocated owner together with a value borrowing from it.
+//!
+//! `Astruct` and `Xstruct` each hand roll the same idea, a field pointing
+//! into the allocation that holds it. [`SelfRefBox`] is the generic form:
+//! the owner is boxed once and never moves, the dependent is built from a
+//! reference to it and dropped before it.
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+
+/// Names the dependent type `Dep<'a>` for every borrow `'a` of the owner.
+pub trait DependentFamily {
+    type Dep<'a>;
+
+    /// Shorten the lifetime of the dependent, implement it as `{ x }`.
+    ///
+    /// That body only compiles when `Dep` is covariant, which is what
+    /// makes handing out `&Dep<'_>` from a longer lived value sound.
+    fn shorten<'long: 'short, 'short>(x: &'short Self::Dep<'long>) -> &'short Self::Dep<'short>;
+}
+
+/// The family `Dep<'a> = &'a T`, e.g. `Ref<str>` for a `String` owner.
+pub struct Ref<T: ?Sized>(PhantomData<fn() -> *const T>);
+
+impl<T: ?Sized + 'static> DependentFamily for Ref<T> {
+    type Dep<'a> = &'a T;
+
+    fn shorten<'long: 'short, 'short>(x: &'short &'long T) -> &'short &'short T {
+        x
+    }
+}
+
+/// An owner `O` on the heap plus a dependent `F::Dep` borrowing from it.
+///
+/// ```
+/// use expr_builder_pattern::self_ref_box::{Ref, SelfRefBox};
+///
+/// let words = SelfRefBox::<String, Ref<str>>::new("hello world".to_string(), |s| {
+///     s.split(' ').next().unwrap()
+/// });
+/// assert_eq!(*words.dependent(), "hello");
+/// assert_eq!(words.owner(), "hello world");
+/// ```
+pub struct SelfRefBox<O, F: DependentFamily> {
+    // The real lifetime is that of `owner`, it is only ever handed out
+    // shortened to a borrow of `self`
+    dependent: ManuallyDrop<F::Dep<'static>>,
+    // From `Box::into_raw`, kept raw so moving the `SelfRefBox` doesn't
+    // assert uniqueness over memory `dependent` borrows
+    owner: NonNull<O>,
+}
+
+impl<O: 'static, F: DependentFamily> SelfRefBox<O, F> {
+    /// Box `owner` and build the dependent from its final address.
+    ///
+    /// The owner can't borrow anything itself, only the dependent borrows.
+    pub fn new(owner: O, dependent: impl for<'a> FnOnce(&'a O) -> F::Dep<'a>) -> Self {
+        let owner = NonNull::from(Box::leak(Box::new(owner)));
+        // Safety: the owner stays allocated and unmoved until drop, which
+        // drops the dependent first, so the unbounded borrow is never
+        // observed past the owner's lifetime.
+        let dependent: F::Dep<'static> = dependent(unsafe { owner.as_ref() });
+        SelfRefBox {
+            dependent: ManuallyDrop::new(dependent),
+            owner,
+        }
+    }
+}
+
+impl<O, F: DependentFamily> SelfRefBox<O, F> {
+    pub fn owner(&self) -> &O {
+        // Safety: allocated for as long as self, only shared borrows exist.
+        unsafe { self.owner.as_ref() }
+    }
+
+    /// The dependent, borrowed no longer than `self`.
+    pub fn dependent(&self) -> &F::Dep<'_> {
+        F::shorten(&self.dependent)
+    }
+}
+
+impl<O, F: DependentFamily> Drop for SelfRefBox<O, F> {
+    fn drop(&mut self) {
+        // Safety: the dependent is dropped before the owner it borrows and
+        // neither is used again, the owner came from `Box::leak`.
+        unsafe {
+            ManuallyDrop::drop(&mut self.dependent);
+            drop(Box::from_raw(self.owner.as_ptr()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use core::ptr;
+
+    /// Astruct expressed as a SelfRefBox: the owner holds the data fields
+    /// and the dependent is the link to `a_u32`.
+    type AstructBox = SelfRefBox<(u8, u32), Ref<u32>>;
+
+    fn astruct(a_u8: u8, a_u32: u32) -> AstructBox {
+        SelfRefBox::new((a_u8, a_u32), |o| &o.1)
+    }
+
+    fn first_word(s: &str) -> SelfRefBox<String, Ref<str>> {
+        SelfRefBox::new(s.to_string(), |s| s.split(' ').next().unwrap())
+    }
+
+    fn pass_through<T>(v: T) -> T {
+        v
+    }
+
+    #[test]
+    fn astruct_link_points_at_a_u32() {
+        let a = astruct(1, 2);
+        assert!(ptr::eq(*a.dependent(), &a.owner().1));
+        assert_eq!(**a.dependent(), 2);
+    }
+
+    #[test]
+    fn string_and_str() {
+        let w = first_word("self ref");
+        assert_eq!(*w.dependent(), "self");
+        assert!(ptr::eq(w.dependent().as_ptr(), w.owner().as_ptr()));
+    }
+
+    #[test]
+    fn survives_function_boundaries() {
+        let a = pass_through(astruct(3, 4));
+        assert!(ptr::eq(*a.dependent(), &a.owner().1));
+        let w = pass_through(first_word("moved around"));
+        assert_eq!(*w.dependent(), "moved");
+    }
+
+    #[test]
+    fn survives_collections() {
+        let mut v: Vec<AstructBox> = (0..100).map(|i| astruct(i as u8, i)).collect();
+        v.swap(0, 99);
+        v.retain(|a| a.owner().1 % 2 == 0);
+        for a in &v {
+            assert!(ptr::eq(*a.dependent(), &a.owner().1));
+        }
+        let words: Vec<_> = ["a b", "c d"].iter().map(|s| first_word(s)).collect();
+        let firsts: Vec<&str> = words.iter().map(|w| *w.dependent()).collect();
+        assert_eq!(firsts, ["a", "c"]);
+    }
+}
@@ -0,0 +1,69 @@
+//! Cloning self-referential structs into fresh allocations.
+//!
+//! A bitwise clone copies the links too, leaving them pointing into the
+//! original. `Astruct`'s `Clone` drops the link instead, [`SelfRefClone`]
+//! keeps it by pointing each one into the clone. The clone is returned
+//! pinned in a box because a value returned by `Self` would move and
+//! break the links it was just given.
+
+use alloc::boxed::Box;
+use core::pin::Pin;
+
+use crate::selfref::{Astruct, Astruct2, Xstruct};
+
+/// Clone whose self references point into the clone, not the original.
+pub trait SelfRefClone {
+    fn self_ref_clone(&self) -> Pin<Box<Self>>;
+}
+
+/// Links `op_a_u32` to the clone's `a_u32` when `self` is linked, an
+/// unlinked `Astruct` clones unlinked.
+impl<T: Clone> SelfRefClone for Astruct<T> {
+    fn self_ref_clone(&self) -> Pin<Box<Self>> {
+        Box::into_pin(self.clone_boxed())
+    }
+}
+
+impl SelfRefClone for Astruct2 {
+    fn self_ref_clone(&self) -> Pin<Box<Self>> {
+        Astruct2::boxed_linked(self.a_u8, self.a_u32)
+    }
+}
+
+impl SelfRefClone for Xstruct {
+    fn self_ref_clone(&self) -> Pin<Box<Self>> {
+        Xstruct::new_boxed(self.f_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn astruct_clone_points_at_itself() {
+        let a = Astruct::boxed_linked(1, 2u32);
+        let c = a.self_ref_clone();
+        assert_eq!((c.a_u8, c.a_u32), (1, 2));
+        assert!(ptr::eq(c.link_ptr().unwrap().as_ptr(), &c.a_u32));
+        drop(a);
+        assert_eq!(c.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn unlinked_astruct_clones_unlinked() {
+        let c = Astruct::boxed_unlinked(1, 2u32).self_ref_clone();
+        assert_eq!(c.link_ptr(), None);
+    }
+
+    #[test]
+    fn astruct2_and_xstruct_clones_validate() {
+        let a = Astruct2::boxed_linked(3, 4);
+        assert_eq!(a.self_ref_clone().validate(), Ok(()));
+        let x = Xstruct::new_boxed(5);
+        let c = x.self_ref_clone();
+        assert_eq!(c.validate(), Ok(()));
+        assert_eq!(*c.as_ref().p(), 5);
+    }
+}
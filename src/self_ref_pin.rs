@@ -0,0 +1,86 @@
+//! An owned, pinned self-referential value.
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::ops::Deref;
+use core::pin::Pin;
+
+use crate::self_ref::SelfRefBuilder;
+
+/// A `Pin<Box<T>>` that is pinned from the moment it is initialized.
+///
+/// There is no window in which the initialized `T` sits in an unpinned
+/// box, so for a `!Unpin` `T` safe code can never move it and break its
+/// self references. Access goes through [`Deref`] or the pinned
+/// [`as_ref`](SelfRefPin::as_ref) / [`as_mut`](SelfRefPin::as_mut).
+pub struct SelfRefPin<T>(Pin<Box<T>>);
+
+impl<T> SelfRefPin<T> {
+    /// Allocate an uninitialized `T`, let `init` write the value fields and
+    /// then the self referential pointers at their final address, then
+    /// `assume_init()` and pin.
+    ///
+    /// # Safety
+    ///
+    /// `init` must initialize every field of `T`, links only after the
+    /// fields they point at.
+    pub unsafe fn new_uninit(init: impl FnOnce(*mut T)) -> Self {
+        SelfRefPin(Box::into_pin(SelfRefBuilder::new().build(init, |_| {})))
+    }
+
+    pub fn as_ref(&self) -> Pin<&T> {
+        self.0.as_ref()
+    }
+
+    pub fn as_mut(&mut self) -> Pin<&mut T> {
+        self.0.as_mut()
+    }
+
+    pub fn into_pin(self) -> Pin<Box<T>> {
+        self.0
+    }
+}
+
+impl<T> Deref for SelfRefPin<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SelfRefPin<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_place::InPlaceInit;
+    use crate::selfref::Xstruct;
+
+    fn xstruct(f: u32) -> SelfRefPin<Xstruct> {
+        unsafe { SelfRefPin::new_uninit(|p: *mut Xstruct| Xstruct::init_in_place(p, f)) }
+    }
+
+    #[test]
+    fn new_uninit_links_xstruct() {
+        let x = xstruct(7);
+        assert_eq!(x.as_ref().f(), 7);
+        assert_eq!(*x.as_ref().p(), 7);
+        assert_eq!(x.validate(), Ok(()));
+    }
+
+    #[test]
+    fn moving_the_wrapper_keeps_the_link() {
+        let x = xstruct(8);
+        let v = alloc::vec![x];
+        let addr = v[0].link_ptr();
+        assert_eq!(v[0].validate(), Ok(()));
+        let x = v.into_iter().next().unwrap().into_pin();
+        assert_eq!(x.link_ptr(), addr);
+        assert_eq!((x.validate(), *x.as_ref().p()), (Ok(()), 8));
+    }
+}
@@ -0,0 +1,1537 @@
+//! Self-referential structs initialized in place with `Box::new_uninit`.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomPinned;
+use core::mem::{self, offset_of, MaybeUninit};
+use core::pin::Pin;
+use core::ptr::{self, addr_of_mut, NonNull};
+
+#[cfg(feature = "std")]
+use crate::errors::UninitError;
+use crate::errors::{AllocError, LinkError, ParseError};
+use crate::in_place::{boxed_in_place, InPlaceInit};
+use crate::maybe_uninit_ext::try_assume_init;
+use crate::rel_ptr::RelPtr;
+use crate::repr_c_ordered_build;
+use crate::self_ref::SelfRefBuilder;
+use crate::uninit_box::UninitDrop;
+use crate::zeroable::{self, Zeroable};
+
+/// A struct whose `op_a_u32` is intended to point at its own `a_u32`.
+///
+/// The payload type `T` defaults to `u32` and only needs to be `Copy` for
+/// the accessors returning it by value.
+///
+/// This struct is self-referential so it needs to use Pin as
+/// Astruct::op_a_u32 should be Some(&Astruct::a_u32):
+///    <https://doc.rust-lang.org/std/pin/index.html>
+///
+/// The link is stored as a `NonNull` rather than a reference with a made
+/// up lifetime and is only readable through accessors. They verify the
+/// pointer still targets this instance's `a_u32` before dereferencing,
+/// so a moved value reports no link instead of dangling.
+///
+/// Through `Pin<&mut Astruct>` the fields project as follows:
+/// - `a_u8` is not structurally pinned, [`a_u8_mut`](Astruct::a_u8_mut)
+///   hands out `&mut u8`.
+/// - `a_u32` is structurally pinned as `op_a_u32` points at it, it is read
+///   with [`a_u32`](Astruct::a_u32) and written with
+///   [`set_a_u32`](Astruct::set_a_u32), never borrowed mutably.
+/// - `op_a_u32` is only readable, through
+///   [`referenced_u32`](Astruct::referenced_u32) and friends.
+///
+/// No builder has a setter for `op_a_u32`, every constructor derives it
+/// from the final allocation. Outside the crate it can only be written
+/// through `unsafe` APIs such as
+/// [`UninitBox::write_field`](crate::UninitBox::write_field), whose
+/// contract requires it to point at this instance's `a_u32`. Safe code
+/// setting it is a compile error:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::Astruct;
+///
+/// let mut a = Astruct::new(1, 2);
+/// a.op_a_u32 = None;
+/// ```
+///
+/// `Astruct` has no `Drop` impl: its drop glue only drops the `T` in
+/// `a_u32` and never reads `op_a_u32`, so a stale link, e.g. in a value
+/// moved out of its box, is never dereferenced while dropping.
+///
+/// `Astruct` is `!Unpin`, so once it is pinned safe code can no longer
+/// move it and invalidate `op_a_u32`, for instance by swapping the
+/// contents of two boxes:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::Astruct;
+///
+/// let mut a = Astruct::boxed_linked(1, 2);
+/// let mut b = Astruct::boxed_linked(3, 4);
+/// core::mem::swap(&mut *a, &mut *b);
+/// ```
+#[derive(UninitDrop)]
+#[repr(C)] // Not necessary but order is maintained as declared
+pub struct Astruct<T = u32> {
+    pub a_u8: u8,
+    pub a_u32: T,
+    pub(crate) op_a_u32: Option<NonNull<T>>, // Point to Astruct::a_u32
+    _pin: PhantomPinned,
+}
+
+// `Option<NonNull<_>>` is guaranteed to use the null niche so an all zero
+// bit pattern is `None`, which is what `boxed_zeroed` relies on.
+const _: () = assert!(size_of::<Option<NonNull<u32>>>() == size_of::<*const u32>());
+
+// The repr(C) layout to_repr_c_bytes, the FFI functions and the layout
+// table document: a_u8, padding to a_u32 at 4, then the pointer sized
+// link. Reordering or retyping a field fails the build here.
+const PTR_ALIGN: usize = align_of::<usize>();
+const _: () = {
+    assert!(offset_of!(Astruct, a_u8) == 0);
+    assert!(offset_of!(Astruct, a_u32) == 4);
+    assert!(offset_of!(Astruct, op_a_u32) == 8);
+    assert!(size_of::<Astruct>() == 8 + size_of::<usize>());
+    assert!(align_of::<Astruct>() == if PTR_ALIGN > 4 { PTR_ALIGN } else { 4 });
+};
+
+// Safety: u8, a zeroable T, a None link and the ZST marker
+unsafe impl<T: Zeroable> Zeroable for Astruct<T> {}
+
+// Safety: writes both values, then the link to the a_u32 just written
+unsafe impl<T> InPlaceInit for Astruct<T> {
+    type Args = (u8, T);
+
+    unsafe fn init_in_place(p: *mut Self, (a_u8, a_u32): (u8, T)) {
+        repr_c_ordered_build!(p => Astruct<T> { a_u8: a_u8, a_u32: a_u32 });
+        let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+        addr_of_mut!((*p).op_a_u32).write(Some(link));
+    }
+}
+
+impl<T> Astruct<T> {
+    /// Create an `Astruct` with `op_a_u32` unset, the link can only be
+    /// established once the value has a stable address.
+    pub const fn new(a_u8: u8, a_u32: T) -> Self {
+        Astruct {
+            a_u8,
+            a_u32,
+            op_a_u32: None,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Box an `Astruct` with `op_a_u32` left as `None`.
+    pub fn boxed_unlinked(a_u8: u8, a_u32: T) -> Box<Self> {
+        Box::new(Astruct::new(a_u8, a_u32))
+    }
+
+    /// Box and pin an `Astruct` with `op_a_u32` pointing at its own `a_u32`.
+    pub fn boxed_linked(a_u8: u8, a_u32: T) -> Pin<Box<Self>> {
+        Box::into_pin(Self::linked_box(a_u8, a_u32))
+    }
+
+    /// The link is initialized using unsafe pointers into the
+    /// `Box::new_uninit` allocation before `assume_init`.
+    ///
+    /// Fields are written through `addr_of_mut!` so no reference to the
+    /// uninitialized allocation is ever created, the values in offset
+    /// order as checked by `repr_c_ordered_build!`.
+    pub(crate) fn linked_box(a_u8: u8, a_u32: T) -> Box<Self> {
+        let mut b = Box::<Astruct<T>>::new_uninit();
+        let a = unsafe {
+            Self::init_in_place(b.as_mut_ptr(), (a_u8, a_u32));
+            try_assume_init(b, |a| a.validate().is_ok())
+        };
+        a.unwrap_or_else(|_| panic!("op_a_u32 was not linked to a_u32"))
+    }
+
+    /// Clone into a new allocation, linking the clone to its own `a_u32`
+    /// when `self` has a link.
+    pub fn clone_boxed(&self) -> Box<Self>
+    where
+        T: Clone,
+    {
+        if self.op_a_u32.is_some() {
+            Self::linked_box(self.a_u8, self.a_u32.clone())
+        } else {
+            Self::boxed_unlinked(self.a_u8, self.a_u32.clone())
+        }
+    }
+
+    /// Borrow `a_u32` through a pinned reference.
+    pub fn a_u32(self: Pin<&Self>) -> &T {
+        &self.get_ref().a_u32
+    }
+
+    /// Mutable access to `a_u8`, which nothing points at.
+    pub fn a_u8_mut(self: Pin<&mut Self>) -> &mut u8 {
+        // Safety: a_u8 is not structurally pinned, handing out &mut u8
+        // can't move anything op_a_u32 depends on.
+        unsafe { &mut self.get_unchecked_mut().a_u8 }
+    }
+
+    /// Point `op_a_u32` at this instance's `a_u32`.
+    ///
+    /// Takes a pinned receiver so the address can't change afterwards,
+    /// this is how a clone or an unlinked value gets its link back.
+    pub fn link(self: Pin<&mut Self>) {
+        // Safety: only a field is written, the value is not moved.
+        let this = unsafe { self.get_unchecked_mut() };
+        // Derived from a mutable borrow so writes to a_u32 through the
+        // pinned value keep the link usable
+        this.op_a_u32 = Some(NonNull::from(&mut this.a_u32));
+    }
+
+    /// Clear `op_a_u32`.
+    pub fn unlink(self: Pin<&mut Self>) {
+        // Safety: only a field is written, the value is not moved.
+        unsafe { self.get_unchecked_mut().op_a_u32 = None }
+    }
+
+    /// Exchange the `a_u8` / `a_u32` payloads of two pinned values.
+    ///
+    /// Unlike `mem::swap` of the whole structs the links are not swapped,
+    /// each linked value is relinked to its own `a_u32` afterwards.
+    pub fn swap_pinned(mut a: Pin<&mut Self>, mut b: Pin<&mut Self>) {
+        {
+            // Safety: only the payload fields are written, neither value is
+            // moved.
+            let (x, y) = unsafe {
+                (
+                    a.as_mut().get_unchecked_mut(),
+                    b.as_mut().get_unchecked_mut(),
+                )
+            };
+            mem::swap(&mut x.a_u8, &mut y.a_u8);
+            mem::swap(&mut x.a_u32, &mut y.a_u32);
+        }
+        for mut s in [a, b] {
+            if s.op_a_u32.is_some() {
+                s.as_mut().link();
+            }
+        }
+    }
+
+    /// Update `a_u32` in place, the link keeps pointing at it.
+    pub fn set_a_u32(mut self: Pin<&mut Self>, v: T) {
+        // Safety: only a field is written, the value is not moved.
+        let this = unsafe { self.as_mut().get_unchecked_mut() };
+        match this.op_a_u32 {
+            // Written through the link so it stays valid for later reads,
+            // a write through `this` would invalidate it
+            // Safety: p points at this instance's initialized a_u32
+            Some(p) if ptr::eq(p.as_ptr(), &this.a_u32) => unsafe { *p.as_ptr() = v },
+            _ => this.a_u32 = v,
+        }
+        debug_assert_eq!(self.as_ref().validate(), Ok(()));
+    }
+
+    /// Copy of the value `op_a_u32` points at, `None` when unlinked.
+    ///
+    /// On a `Pin<Box<Astruct>>` call it directly or via `as_ref()`.
+    pub fn referenced_u32(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        self.referenced_u32_ref().copied()
+    }
+
+    /// The raw link, for inspecting where it points.
+    pub fn link_ptr(&self) -> Option<NonNull<T>> {
+        self.op_a_u32
+    }
+
+    /// Check that `op_a_u32`, when set, points at this instance's `a_u32`.
+    ///
+    /// An unlinked value is valid, a moved one still carrying the old
+    /// address is not.
+    pub fn validate(&self) -> Result<(), LinkError> {
+        match self.op_a_u32 {
+            Some(p) => check_link("op_a_u32", &self.a_u32, p.as_ptr()),
+            None => Ok(()),
+        }
+    }
+
+    /// True only when `op_a_u32` is set and points at this instance's
+    /// `a_u32`. Unlike [`validate`](Astruct::validate) an unlinked value
+    /// is not consistent.
+    pub fn is_self_consistent(&self) -> bool {
+        matches!(self.op_a_u32, Some(p) if ptr::eq(p.as_ptr(), &self.a_u32))
+    }
+
+    /// Panic in debug builds unless [`is_self_consistent`](Astruct::is_self_consistent).
+    #[track_caller]
+    #[inline]
+    pub fn debug_assert_self_consistent(&self) {
+        debug_assert!(
+            self.is_self_consistent(),
+            "Astruct is not self consistent: op_a_u32={:?} but &a_u32={:p}",
+            self.op_a_u32,
+            &self.a_u32
+        );
+    }
+
+    /// The value `op_a_u32` points at, borrowed from `self`.
+    ///
+    /// `None` when unlinked or when the link no longer targets this
+    /// instance's `a_u32`, so it never dangles or panics.
+    pub fn referenced_u32_ref(&self) -> Option<&T> {
+        match self.op_a_u32 {
+            // Safety: the pointer targets our own a_u32 which is
+            // initialized and borrowed for the lifetime of &self.
+            Some(p) if ptr::eq(p.as_ptr(), &self.a_u32) => Some(unsafe { p.as_ref() }),
+            _ => None,
+        }
+    }
+
+    /// The value `op_a_u32` points at, borrowed for as long as the pin.
+    #[deprecated(note = "use `referenced_u32_ref`, which also works unpinned")]
+    pub fn linked(self: Pin<&Self>) -> Option<&T> {
+        self.get_ref().referenced_u32_ref()
+    }
+
+    /// Copy of the value `op_a_u32` points at, `None` when unlinked.
+    #[deprecated(note = "renamed to `referenced_u32`")]
+    pub fn linked_value(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        self.referenced_u32()
+    }
+}
+
+impl Astruct {
+    /// Where the fields land in memory, from `offset_of!`.
+    pub const fn layout() -> LayoutInfo {
+        LayoutInfo {
+            size: size_of::<Astruct>(),
+            align: align_of::<Astruct>(),
+            offsets: astruct_offsets(),
+        }
+    }
+
+    /// Size of the `#[repr(C)]` byte layout used by
+    /// [`from_repr_c_bytes`](Astruct::from_repr_c_bytes).
+    pub const REPR_C_SIZE: usize = size_of::<Astruct>();
+
+    /// Rebuild a linked `Astruct` from its `#[repr(C)]` bytes in native
+    /// byte order, e.g. as received from a peer.
+    ///
+    /// `a_u8` and `a_u32` are read at their `offset_of!` offsets, padding
+    /// and whatever address the `op_a_u32` bytes hold are ignored and the
+    /// link points at the new copy's `a_u32`.
+    pub fn from_repr_c_bytes(bytes: &[u8]) -> Result<Pin<Box<Self>>, ParseError> {
+        let bytes: &[u8; Self::REPR_C_SIZE] = bytes
+            .get(..Self::REPR_C_SIZE)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(ParseError::TooShort {
+                needed: Self::REPR_C_SIZE,
+                found: bytes.len(),
+            })?;
+        let a_u8 = bytes[offset_of!(Astruct, a_u8)];
+        let at = offset_of!(Astruct, a_u32);
+        let a_u32 = u32::from_ne_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+        Ok(Self::boxed_linked(a_u8, a_u32))
+    }
+
+    /// The `#[repr(C)]` bytes of `self`, the inverse of
+    /// [`from_repr_c_bytes`](Astruct::from_repr_c_bytes).
+    ///
+    /// The result is [`REPR_C_SIZE`](Astruct::REPR_C_SIZE) bytes long with
+    /// each field at its `offset_of!` offset in native byte order, on
+    /// 64-bit targets:
+    ///
+    /// | bytes  | contents                 |
+    /// |--------|--------------------------|
+    /// | 0      | `a_u8`                   |
+    /// | 1..4   | padding, zero            |
+    /// | 4..8   | `a_u32`                  |
+    /// | 8..16  | `op_a_u32`, always zero  |
+    ///
+    /// The link is written as zeros since the address is meaningless once
+    /// the bytes leave this instance.
+    pub fn to_repr_c_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::REPR_C_SIZE];
+        bytes[offset_of!(Astruct, a_u8)] = self.a_u8;
+        let at = offset_of!(Astruct, a_u32);
+        bytes[at..at + 4].copy_from_slice(&self.a_u32.to_ne_bytes());
+        bytes
+    }
+
+    /// `a_u8` followed by `a_u32` in little endian, without padding or the
+    /// link, so equal values give equal bytes whatever the allocation or
+    /// target. Meant for hashing and checksums, not for
+    /// [`from_repr_c_bytes`](Astruct::from_repr_c_bytes).
+    pub fn stable_bytes(&self) -> [u8; 5] {
+        let [b0, b1, b2, b3] = self.a_u32.to_le_bytes();
+        [self.a_u8, b0, b1, b2, b3]
+    }
+
+    /// One allocation holding an `Astruct` per `(a_u8, a_u32)` of `init`,
+    /// each linked to its own `a_u32`.
+    ///
+    /// Built with `Box::new_uninit_slice`, every element is initialized
+    /// in its final slot before the slice is assumed initialized.
+    pub fn boxed_slice_linked(init: &[(u8, u32)]) -> Pin<Box<[Self]>> {
+        let mut slots = Box::<[Astruct]>::new_uninit_slice(init.len());
+        for (slot, &args) in slots.iter_mut().zip(init) {
+            // Safety: the slot is in the final allocation
+            unsafe { Self::init_in_place(slot.as_mut_ptr(), args) };
+        }
+        // Safety: every element was initialized above
+        Box::into_pin(unsafe { slots.assume_init() })
+    }
+
+    /// Box an `Astruct` from zeroed memory.
+    ///
+    /// Sound as `Astruct` is [`Zeroable`]: the integers are 0 and
+    /// `Option<NonNull<u32>>` is `None`.
+    pub fn boxed_zeroed() -> Box<Self> {
+        zeroable::boxed_zeroed()
+    }
+}
+
+/// Shows where `op_a_u32` points: `self(<a_u32>)` when at this instance's
+/// `a_u32`, `other(<addr>)` when elsewhere, or `None`.
+impl<T: fmt::Debug> fmt::Debug for Astruct<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Link<'a, T>(&'a Astruct<T>);
+
+        impl<T: fmt::Debug> fmt::Debug for Link<'_, T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.0.op_a_u32 {
+                    None => f.write_str("None"),
+                    Some(p) if ptr::eq(p.as_ptr(), &self.0.a_u32) => {
+                        write!(f, "self({:?})", self.0.a_u32)
+                    }
+                    Some(p) => write!(f, "other({:p})", p),
+                }
+            }
+        }
+
+        f.debug_struct("Astruct")
+            .field("a_u8", &self.a_u8)
+            .field("a_u32", &self.a_u32)
+            .field("op_a_u32", &Link(self))
+            .finish()
+    }
+}
+
+/// The persistable form of an [`Astruct`]: its values and whether it was
+/// linked, never the address in `op_a_u32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AstructRecord<T = u32> {
+    pub a_u8: u8,
+    pub a_u32: T,
+    pub linked: bool,
+}
+
+impl<T> Astruct<T> {
+    /// The values of `self` as an [`AstructRecord`].
+    pub fn to_record(&self) -> AstructRecord<T>
+    where
+        T: Clone,
+    {
+        AstructRecord {
+            a_u8: self.a_u8,
+            a_u32: self.a_u32.clone(),
+            linked: self.op_a_u32.is_some(),
+        }
+    }
+
+    /// Rebuild from `record`, linking into the new allocation when it was
+    /// linked.
+    pub fn from_record(record: AstructRecord<T>) -> Pin<Box<Self>> {
+        if record.linked {
+            Self::boxed_linked(record.a_u8, record.a_u32)
+        } else {
+            Box::into_pin(Self::boxed_unlinked(record.a_u8, record.a_u32))
+        }
+    }
+}
+
+/// Copies the data fields and leaves the clone unlinked.
+///
+/// The clone is returned by value so its address isn't known yet, copying
+/// `op_a_u32` would make it point into the original. Use
+/// [`clone_boxed`](Astruct::clone_boxed) for a linked copy.
+impl<T: Clone> Clone for Astruct<T> {
+    fn clone(&self) -> Self {
+        Astruct::new(self.a_u8, self.a_u32.clone())
+    }
+}
+
+/// Equal when `a_u8`, `a_u32` and whether `op_a_u32` is set match, the
+/// address in the link is ignored as two values can never share it.
+impl<T: PartialEq> PartialEq for Astruct<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.a_u8 == other.a_u8
+            && self.a_u32 == other.a_u32
+            && self.op_a_u32.is_some() == other.op_a_u32.is_some()
+    }
+}
+
+impl<T: Eq> Eq for Astruct<T> {}
+
+/// Hashes the same parts [`PartialEq`] compares.
+impl<T: Hash> Hash for Astruct<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.a_u8.hash(state);
+        self.a_u32.hash(state);
+        self.op_a_u32.is_some().hash(state);
+    }
+}
+
+// Safety: `op_a_u32` only ever points at the struct's own `a_u32`, so
+// sending or sharing an `Astruct` amounts to sending or sharing its `T`,
+// as for `Box<T>`. Moving the value breaks the link on any thread, that
+// is what `Pin` is for and unrelated to `Send`.
+unsafe impl<T: Send> Send for Astruct<T> {}
+unsafe impl<T: Sync> Sync for Astruct<T> {}
+
+/// Byte offsets of the fields of an [`Astruct`], see [`astruct_offsets`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AstructOffsets {
+    pub a_u8: usize,
+    pub a_u32: usize,
+    pub op_a_u32: usize,
+}
+
+/// The `#[repr(C)]` layout of [`Astruct`] from `offset_of!`, no instance
+/// needed.
+pub const fn astruct_offsets() -> AstructOffsets {
+    AstructOffsets {
+        a_u8: offset_of!(Astruct, a_u8),
+        a_u32: offset_of!(Astruct, a_u32),
+        op_a_u32: offset_of!(Astruct, op_a_u32),
+    }
+}
+
+/// Size, alignment and field offsets of [`Astruct`], see
+/// [`Astruct::layout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayoutInfo {
+    pub size: usize,
+    pub align: usize,
+    pub offsets: AstructOffsets,
+}
+
+/// One line per field in offset order, e.g. on 64-bit targets:
+///
+/// ```text
+/// Astruct size=16 align=8
+///   a_u8     @ 0..1
+///   a_u32    @ 4..8
+///   op_a_u32 @ 8..16
+/// ```
+impl fmt::Display for LayoutInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Astruct size={} align={}", self.size, self.align)?;
+        let fields = [
+            ("a_u8", self.offsets.a_u8, size_of::<u8>()),
+            ("a_u32", self.offsets.a_u32, size_of::<u32>()),
+            (
+                "op_a_u32",
+                self.offsets.op_a_u32,
+                size_of::<Option<NonNull<u32>>>(),
+            ),
+        ];
+        for (i, (name, offset, size)) in fields.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "  {:<8} @ {}..{}", name, offset, offset + size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Like [`Astruct`] but with two links into the same allocation,
+/// `op_a_u8` points at `a_u8` and `op_a_u32` at `a_u32`.
+#[derive(Debug)]
+#[repr(C)] // Not necessary but order is maintained as declared
+pub struct Astruct2 {
+    pub a_u8: u8,
+    pub a_u32: u32,
+    pub(crate) op_a_u8: Option<NonNull<u8>>, // Point to Astruct2::a_u8
+    pub(crate) op_a_u32: Option<NonNull<u32>>, // Point to Astruct2::a_u32
+    _pin: PhantomPinned,
+}
+
+// Safety: both links point into the struct itself, see Astruct
+unsafe impl Send for Astruct2 {}
+unsafe impl Sync for Astruct2 {}
+
+impl Astruct2 {
+    /// Box and pin an `Astruct2` with both links pointing into itself.
+    pub fn boxed_linked(a_u8: u8, a_u32: u32) -> Pin<Box<Self>> {
+        Box::into_pin(Self::linked_box(a_u8, a_u32))
+    }
+
+    /// Box and pin an `Astruct2` with only `op_a_u8` linked, `op_a_u32`
+    /// is `None`. Built by [`AstructBuilder::point_at_u8`].
+    ///
+    /// [`AstructBuilder::point_at_u8`]: crate::AstructBuilder::point_at_u8
+    pub fn boxed_linked_u8(a_u8: u8, a_u32: u32) -> Pin<Box<Self>> {
+        let mut a = Self::linked_box(a_u8, a_u32);
+        a.op_a_u32 = None;
+        Box::into_pin(a)
+    }
+
+    /// Both links are written in the same `Box::new_uninit` pass, after
+    /// the values they point at.
+    pub(crate) fn linked_box(a_u8: u8, a_u32: u32) -> Box<Self> {
+        let a = unsafe {
+            SelfRefBuilder::<Astruct2>::new().build(
+                |p| {
+                    addr_of_mut!((*p).a_u8).write(a_u8);
+                    addr_of_mut!((*p).a_u32).write(a_u32);
+                },
+                |p| {
+                    let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u8));
+                    addr_of_mut!((*p).op_a_u8).write(Some(link));
+                    let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+                    addr_of_mut!((*p).op_a_u32).write(Some(link));
+                },
+            )
+        };
+        debug_assert_eq!(a.validate(), Ok(()));
+        a
+    }
+
+    /// Copy of the value `op_a_u8` points at, `None` when not linked here.
+    pub fn linked_a_u8(&self) -> Option<u8> {
+        match self.op_a_u8 {
+            // Safety: the pointer targets our own initialized a_u8.
+            Some(p) if ptr::eq(p.as_ptr(), &self.a_u8) => Some(unsafe { *p.as_ptr() }),
+            _ => None,
+        }
+    }
+
+    /// Copy of the value `op_a_u32` points at, `None` when not linked here.
+    pub fn linked_a_u32(&self) -> Option<u32> {
+        match self.op_a_u32 {
+            // Safety: the pointer targets our own initialized a_u32.
+            Some(p) if ptr::eq(p.as_ptr(), &self.a_u32) => Some(unsafe { *p.as_ptr() }),
+            _ => None,
+        }
+    }
+
+    /// The raw links, for inspecting where they point.
+    pub fn link_ptrs(&self) -> (Option<NonNull<u8>>, Option<NonNull<u32>>) {
+        (self.op_a_u8, self.op_a_u32)
+    }
+
+    /// Check both links, reporting the first one that is off.
+    pub fn validate(&self) -> Result<(), LinkError> {
+        if let Some(p) = self.op_a_u8 {
+            check_link("op_a_u8", &self.a_u8, p.as_ptr())?;
+        }
+        if let Some(p) = self.op_a_u32 {
+            check_link("op_a_u32", &self.a_u32, p.as_ptr())?;
+        }
+        Ok(())
+    }
+}
+
+/// Like [`Astruct`] but the link is a [`RelPtr`], so it can be moved
+/// freely, returned by value or stored in a `Vec` without `Pin`: the
+/// offset from `op_a_u32` to `a_u32` is the same wherever it lives.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)] // Not necessary but order is maintained as declared
+pub struct AstructRel {
+    pub a_u8: u8,
+    pub a_u32: u32,
+    op_a_u32: RelPtr<u32>,
+}
+
+impl AstructRel {
+    /// `op_a_u32` pointing at `a_u32`.
+    pub fn new_linked(a_u8: u8, a_u32: u32) -> AstructRel {
+        let mut a = AstructRel {
+            a_u8,
+            a_u32,
+            op_a_u32: RelPtr::null(),
+        };
+        let target = &a.a_u32 as *const u32;
+        a.op_a_u32.set(target);
+        a
+    }
+
+    /// The value `op_a_u32` points at, wherever `self` has been moved to.
+    pub fn linked_value(&self) -> Option<u32> {
+        // Safety: new_linked set the offset to a_u32 of the same struct,
+        // which moves along with it
+        self.op_a_u32.get_in(self).map(|p| unsafe { *p.as_ptr() })
+    }
+
+    /// Check that `op_a_u32` points at this instance's `a_u32`.
+    pub fn validate(&self) -> Result<(), LinkError> {
+        match self.op_a_u32.get() {
+            Some(p) => check_link("op_a_u32", &self.a_u32, p.as_ptr()),
+            None => Err(LinkError {
+                field: "op_a_u32",
+                expected: &self.a_u32 as *const u32 as usize,
+                found: 0,
+            }),
+        }
+    }
+}
+
+crate::pin_project! {
+    #[project = XstructProjection]
+    /// Self referental structure without using Option, as with Astruct::op_a_u32
+    /// Xstruct::p points to Xstruct::f so needs to use Pin:
+    ///    <https://doc.rust-lang.org/std/pin/index.html>
+    ///
+    /// The link is a private `NonNull` and only read through
+    /// [`p`](Xstruct::p), which borrows the target from `self`, so it can't
+    /// outlive the value. `Xstruct` is `!Unpin` and [`f`](Xstruct::f) /
+    /// [`p`](Xstruct::p) read it through `Pin<&Self>`. A pinned `Xstruct`
+    /// can't be moved out from under it:
+    ///
+    /// ```compile_fail
+    /// use expr_builder_pattern::Xstruct;
+    ///
+    /// let mut a = Xstruct::new_boxed(1);
+    /// let mut b = Xstruct::new_boxed(2);
+    /// core::mem::swap(&mut *a, &mut *b);
+    /// ```
+    ///
+    /// Both fields are private, so safe code can't read the raw link or
+    /// overwrite `f` from under it:
+    ///
+    /// ```compile_fail
+    /// let x = expr_builder_pattern::Xstruct::new_boxed(1);
+    /// let _ = x.p;
+    /// ```
+    ///
+    /// [`project`](Xstruct::project) borrows `f` as `&mut u32` and the
+    /// structurally pinned `p` as `Pin<&mut NonNull<u32>>`.
+    #[derive(Debug)]
+    #[repr(C)] // Not necessary but order is maintained as declared
+    pub struct Xstruct {
+        f: u32,
+        #[pin]
+        p: NonNull<u32>, // Point to Xstruct::f
+        _pin: PhantomPinned,
+    }
+}
+
+// Safety: p points into the struct itself, see Astruct
+unsafe impl Send for Xstruct {}
+unsafe impl Sync for Xstruct {}
+
+// f at 0, p at the next pointer aligned offset
+const _: () = {
+    const P: usize = if PTR_ALIGN > 4 { PTR_ALIGN } else { 4 };
+    assert!(offset_of!(Xstruct, f) == 0);
+    assert!(offset_of!(Xstruct, p) == P);
+    assert!(size_of::<Xstruct>() == P + size_of::<usize>());
+    assert!(align_of::<Xstruct>() == P);
+};
+
+// Safety: writes f, then p pointing at the f just written
+unsafe impl InPlaceInit for Xstruct {
+    type Args = u32;
+
+    unsafe fn init_in_place(p: *mut Self, f: u32) {
+        addr_of_mut!((*p).f).write(f);
+        let link = NonNull::new_unchecked(addr_of_mut!((*p).f));
+        addr_of_mut!((*p).p).write(link);
+    }
+}
+
+impl Xstruct {
+    /// Allocate an `Xstruct` on the heap with `p` pointing at its own `f`.
+    pub fn boxed(f: u32) -> Box<Xstruct> {
+        let x = boxed_in_place::<Xstruct>(f);
+        debug_assert_eq!(x.validate(), Ok(()));
+        x
+    }
+
+    /// `f` read through the pin.
+    pub fn f(self: Pin<&Self>) -> u32 {
+        self.get_ref().f
+    }
+
+    /// `f` for crate code holding an unpinned `&Xstruct`, e.g. to copy it.
+    pub(crate) fn f_value(&self) -> u32 {
+        self.f
+    }
+
+    /// The value `p` points at, borrowed for no longer than the pin.
+    ///
+    /// # Panics
+    ///
+    /// If `p` doesn't point at this instance's `f`, e.g. after the value
+    /// was moved out of the unpinned box from [`boxed`](Xstruct::boxed).
+    pub fn p(self: Pin<&Self>) -> &u32 {
+        let x = self.get_ref();
+        assert!(
+            ptr::eq(x.p.as_ptr(), &x.f),
+            "Xstruct::p does not point at its own f"
+        );
+        // Safety: p targets our own f, borrowed from self
+        unsafe { x.p.as_ref() }
+    }
+
+    /// The raw link, for inspecting where it points.
+    pub fn link_ptr(&self) -> NonNull<u32> {
+        self.p
+    }
+
+    /// Pinned counterpart of [`boxed`](Xstruct::boxed).
+    pub fn new_boxed(f: u32) -> Pin<Box<Xstruct>> {
+        Box::into_pin(Self::boxed(f))
+    }
+
+    /// Copy `f` into a fresh allocation whose `p` points at the new `f`.
+    ///
+    /// `p` can't be left unset, so unlike [`Astruct`] there is no by-value
+    /// clone, only this one building the link anew.
+    pub fn clone_relinked(self: Pin<&Self>) -> Pin<Box<Xstruct>> {
+        Self::new_boxed(self.f)
+    }
+
+    /// Check that `p` points at this instance's `f`.
+    pub fn validate(&self) -> Result<(), LinkError> {
+        check_link("p", &self.f, self.p.as_ptr())
+    }
+}
+
+fn check_link<T>(field: &'static str, target: &T, found: *const T) -> Result<(), LinkError> {
+    if ptr::eq(target, found) {
+        Ok(())
+    } else {
+        Err(LinkError {
+            field,
+            expected: target as *const T as usize,
+            found: found as usize,
+        })
+    }
+}
+
+/// Simple example of using new_uninit from the documentation:
+///   <https://doc.rust-lang.org/std/boxed/struct.Box.html#method.new_uninit>
+///
+/// The write and `assume_init` now happen in [`boxed_in_place`].
+pub fn boxed_u32(v: u32) -> Box<u32> {
+    boxed_in_place(v)
+}
+
+/// [`Astruct::boxed_linked`] returning an error instead of aborting when
+/// the allocation fails.
+///
+/// `Box::try_new_uninit` isn't stable, so the memory comes straight from
+/// the global allocator. The values and then the link are written only
+/// once it is known to have succeeded.
+pub fn try_build_astruct(a_u8: u8, a_u32: u32) -> Result<Pin<Box<Astruct>>, AllocError> {
+    // Never zero sized, a_u8 alone is a byte
+    let layout = Layout::new::<Astruct>();
+    let p = unsafe { alloc::alloc::alloc(layout) }.cast::<MaybeUninit<Astruct>>();
+    if p.is_null() {
+        return Err(AllocError {
+            size: layout.size(),
+            align: layout.align(),
+        });
+    }
+    unsafe {
+        // Safety: allocated by the global allocator with Astruct's layout
+        let mut b = Box::from_raw(p);
+        let p = b.as_mut_ptr();
+        repr_c_ordered_build!(p => Astruct { a_u8: a_u8, a_u32: a_u32 });
+        let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+        addr_of_mut!((*p).op_a_u32).write(Some(link));
+        match try_assume_init(b, |a| a.validate().is_ok()) {
+            Ok(a) => Ok(Box::into_pin(a)),
+            Err(_) => panic!("op_a_u32 was not linked to a_u32"),
+        }
+    }
+}
+
+/// Checked counterpart of writing through an uninitialized `&mut Vec<u32>`:
+/// the `Vec` is fully initialized before it is handed out.
+#[cfg(feature = "std")]
+pub fn try_init_vec() -> Result<Box<Vec<u32>>, UninitError> {
+    try_init_boxed(Vec::new)
+}
+
+/// Run `init` and move its result into a fresh `Box`.
+///
+/// A panic in `init` is caught and reported as
+/// [`UninitError::InitPanicked`], the uninitialized box is freed without
+/// ever being read.
+#[cfg(feature = "std")]
+pub fn try_init_boxed<T>(init: impl FnOnce() -> T) -> Result<Box<T>, UninitError> {
+    let mut b = Box::<T>::new_uninit();
+    // Nothing observes a half finished `T`, on panic only the empty box is
+    // dropped.
+    let v = std::panic::catch_unwind(std::panic::AssertUnwindSafe(init))
+        .map_err(|_| UninitError::InitPanicked)?;
+    b.write(v);
+    // Safety: just written.
+    Ok(unsafe { b.assume_init() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Counts how often it is dropped, as the a_u32 of an Astruct
+    struct DropCount(Rc<Cell<usize>>);
+
+    impl Drop for DropCount {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn boxed_unlinked_has_no_link() {
+        let a = Astruct::boxed_unlinked(1, 123);
+        assert_eq!(a.a_u8, 1);
+        assert_eq!(a.a_u32, 123);
+        assert!(a.op_a_u32.is_none());
+        assert_eq!(a.referenced_u32(), None);
+    }
+
+    #[test]
+    fn boxed_linked_points_at_own_a_u32() {
+        let a = Astruct::boxed_linked(4, 456);
+        assert_eq!(a.a_u8, 4);
+        assert_eq!(a.a_u32, 456);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!(a.referenced_u32(), Some(456));
+    }
+
+    #[test]
+    fn boxed_linked_survives_moving_the_box() {
+        let a = Astruct::boxed_linked(4, 456);
+        let moved = a;
+        assert!(ptr::eq(moved.link_ptr().unwrap().as_ptr(), &moved.a_u32));
+    }
+
+    #[test]
+    fn referenced_u32_follows_set_a_u32() {
+        let mut a = Astruct::boxed_linked(4, 456);
+        let link = a.link_ptr();
+        a.as_mut().set_a_u32(789);
+        assert_eq!(a.a_u32, 789);
+        assert_eq!(a.referenced_u32(), Some(789));
+        assert_eq!(a.link_ptr(), link);
+        a.as_mut().set_a_u32(0);
+        assert_eq!(a.referenced_u32(), Some(0));
+    }
+
+    #[test]
+    fn swap_pinned_exchanges_payloads_and_keeps_links() {
+        let mut a = Astruct::boxed_linked(1, 2u32);
+        let mut b = Astruct::boxed_linked(3, 4u32);
+        Astruct::swap_pinned(a.as_mut(), b.as_mut());
+        assert_eq!((a.a_u8, a.a_u32), (3, 4));
+        assert_eq!((b.a_u8, b.a_u32), (1, 2));
+        assert_eq!((a.validate(), b.validate()), (Ok(()), Ok(())));
+        assert_eq!((a.referenced_u32(), b.referenced_u32()), (Some(4), Some(2)));
+    }
+
+    #[test]
+    fn swap_pinned_leaves_unlinked_unlinked() {
+        let mut a = Box::into_pin(Astruct::boxed_unlinked(1, 2u32));
+        let mut b = Astruct::boxed_linked(3, 4u32);
+        Astruct::swap_pinned(a.as_mut(), b.as_mut());
+        assert_eq!((a.a_u32, a.link_ptr()), (4, None));
+        assert_eq!(b.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn record_round_trip_relinks() {
+        let a = Astruct::boxed_linked(1, 2u32);
+        let r = a.to_record();
+        assert_eq!(
+            r,
+            AstructRecord {
+                a_u8: 1,
+                a_u32: 2,
+                linked: true
+            }
+        );
+        drop(a);
+        let b = Astruct::from_record(r);
+        assert_eq!(b.validate(), Ok(()));
+        assert_eq!(b.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn record_of_unlinked_stays_unlinked() {
+        let r = Astruct::new(1, 2u32).to_record();
+        assert!(!r.linked);
+        let b = Astruct::from_record(r);
+        assert_eq!((b.a_u8, b.a_u32, b.link_ptr()), (1, 2, None));
+    }
+
+    #[test]
+    fn debug_shows_link_target() {
+        let a = Astruct::boxed_linked(1, 2u32);
+        assert_eq!(
+            format!("{:?}", a),
+            "Astruct { a_u8: 1, a_u32: 2, op_a_u32: self(2) }"
+        );
+        assert_eq!(
+            format!("{:?}", Astruct::new(1, 2u32)),
+            "Astruct { a_u8: 1, a_u32: 2, op_a_u32: None }"
+        );
+
+        let mut wrong = Astruct::boxed_unlinked(3, 4u32);
+        wrong.op_a_u32 = Some(NonNull::from(&a.a_u32));
+        assert_eq!(
+            format!("{:?}", wrong),
+            format!(
+                "Astruct {{ a_u8: 3, a_u32: 4, op_a_u32: other({:p}) }}",
+                &a.a_u32
+            )
+        );
+    }
+
+    #[test]
+    fn moved_out_value_reports_no_link() {
+        let boxed = Astruct::linked_box(1, 2);
+        let moved: Astruct = *boxed;
+        assert!(moved.link_ptr().is_some());
+        assert_eq!(moved.referenced_u32(), None);
+    }
+
+    #[test]
+    fn pinned_accessors() {
+        let a = Astruct::boxed_linked(4, 456);
+        assert_eq!(*a.as_ref().a_u32(), 456);
+        assert!(ptr::eq(a.as_ref().referenced_u32_ref().unwrap(), &a.a_u32));
+        let b = a.clone();
+        assert_eq!(b.referenced_u32(), None);
+        let unlinked = Box::into_pin(Astruct::boxed_unlinked(1, 2));
+        assert_eq!(unlinked.as_ref().referenced_u32_ref(), None);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_accessors_still_read_the_link() {
+        let a = Astruct::boxed_linked(4, 456);
+        assert!(ptr::eq(a.as_ref().linked().unwrap(), &a.a_u32));
+        assert_eq!(a.linked_value(), Some(456));
+        let moved = *Astruct::linked_box(1, 2);
+        assert_eq!(moved.linked_value(), None);
+    }
+
+    #[test]
+    fn boxed_slice_links_each_element_to_itself() {
+        let init: Vec<(u8, u32)> = (0..1000).map(|i| (i as u8, i)).collect();
+        let s = Astruct::boxed_slice_linked(&init);
+        assert_eq!(s.len(), 1000);
+        for (a, &(a_u8, a_u32)) in s.iter().zip(&init) {
+            assert_eq!((a.a_u8, a.a_u32), (a_u8, a_u32));
+            assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+            // Inside this element, not a neighbor
+            let start = a as *const Astruct as usize;
+            let link = a.link_ptr().unwrap().as_ptr() as usize;
+            assert!((start..start + size_of::<Astruct>()).contains(&link));
+            assert_eq!(a.referenced_u32(), Some(a_u32));
+        }
+    }
+
+    #[test]
+    fn boxed_slice_of_nothing() {
+        let s = Astruct::boxed_slice_linked(&[]);
+        assert!(s.is_empty());
+        let s = Astruct::boxed_slice_linked(&[(1, 2)]);
+        assert_eq!(s[0].referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn boxed_zeroed_is_all_zero() {
+        let z = Astruct::boxed_zeroed();
+        assert_eq!(z.a_u8, 0);
+        assert_eq!(z.a_u32, 0);
+        assert!(z.op_a_u32.is_none());
+        assert_eq!(z.referenced_u32(), None);
+    }
+
+    #[test]
+    fn xstruct_boxed_points_at_own_f() {
+        let x = Box::into_pin(Xstruct::boxed(47));
+        assert_eq!(x.f, 47);
+        assert!(ptr::eq(x.p.as_ptr(), &x.f));
+        assert_eq!(*x.as_ref().p(), 47);
+    }
+
+    #[test]
+    fn boxed_u32_holds_value() {
+        assert_eq!(*boxed_u32(5), 5);
+    }
+
+    // Runs every constructor and reads back through each link, meant for
+    // `cargo +nightly miri test` to check none of them is UB.
+    #[test]
+    fn every_constructor_reads_back() {
+        let a = Astruct::new(1, 2);
+        assert_eq!((a.a_u8, a.a_u32, a.referenced_u32()), (1, 2, None));
+
+        let a = Astruct::boxed_unlinked(3, 4);
+        assert_eq!(a.referenced_u32(), None);
+
+        let mut a = Astruct::boxed_linked(5, 6);
+        assert_eq!(a.as_ref().referenced_u32_ref(), Some(&6));
+        a.as_mut().set_a_u32(7);
+        assert_eq!(a.as_ref().referenced_u32_ref(), Some(&7));
+
+        let a = Astruct::linked_box(8, 9);
+        assert_eq!(a.referenced_u32(), Some(9));
+
+        let z = Astruct::boxed_zeroed();
+        assert_eq!((z.a_u8, z.a_u32, z.link_ptr()), (0, 0, None));
+
+        let x = Xstruct::new_boxed(10);
+        assert_eq!(*x.as_ref().p(), 10);
+
+        assert_eq!(*boxed_u32(11), 11);
+        assert!(try_init_vec().unwrap().is_empty());
+    }
+
+    #[test]
+    fn validate_accepts_linked_and_unlinked() {
+        assert_eq!(Astruct::boxed_linked(1, 2).validate(), Ok(()));
+        assert_eq!(Astruct::boxed_unlinked(1, 2).validate(), Ok(()));
+        assert_eq!(Astruct::boxed_zeroed().validate(), Ok(()));
+        assert_eq!(Xstruct::boxed(3).validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_corrupted_link() {
+        let mut a = Astruct::boxed_unlinked(1, 2);
+        // Inside the struct but at the offset of a_u8 instead of a_u32
+        let wrong = NonNull::new(addr_of_mut!(a.a_u8).cast::<u32>());
+        a.op_a_u32 = wrong;
+        let err = a.validate().unwrap_err();
+        assert_eq!(err.field, "op_a_u32");
+        assert_eq!(err.expected, &a.a_u32 as *const u32 as usize);
+        assert_eq!(err.found, wrong.unwrap().as_ptr() as usize);
+        assert_eq!(err.expected - err.found, 4);
+    }
+
+    #[test]
+    fn validate_reports_moved_value() {
+        let a = Astruct::linked_box(1, 2);
+        let moved = *a;
+        assert!(moved.validate().is_err());
+    }
+
+    #[test]
+    fn validate_reports_corrupted_xstruct() {
+        let other = 7u32;
+        let mut x = Xstruct::boxed(3);
+        x.p = NonNull::from(&other);
+        let err = x.validate().unwrap_err();
+        assert_eq!(err.field, "p");
+        assert_eq!(err.found, &other as *const u32 as usize);
+    }
+
+    #[test]
+    fn astruct2_links_both_fields() {
+        let a = Astruct2::boxed_linked(1, 2);
+        let (op_a_u8, op_a_u32) = a.link_ptrs();
+        assert!(ptr::eq(op_a_u8.unwrap().as_ptr(), &a.a_u8));
+        assert!(ptr::eq(op_a_u32.unwrap().as_ptr(), &a.a_u32));
+        assert_eq!((a.linked_a_u8(), a.linked_a_u32()), (Some(1), Some(2)));
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn astruct2_moved_out_reports_both_broken() {
+        let moved = *Astruct2::linked_box(1, 2);
+        assert_eq!((moved.linked_a_u8(), moved.linked_a_u32()), (None, None));
+        assert_eq!(moved.validate().unwrap_err().field, "op_a_u8");
+    }
+
+    #[test]
+    fn astruct2_reports_only_the_corrupted_link() {
+        let other = Astruct2::linked_box(8, 9);
+
+        let mut a = Astruct2::linked_box(1, 2);
+        a.op_a_u32 = Some(NonNull::from(&other.a_u32));
+        assert_eq!((a.linked_a_u8(), a.linked_a_u32()), (Some(1), None));
+        let err = a.validate().unwrap_err();
+        assert_eq!(err.field, "op_a_u32");
+        assert_eq!(err.expected, &a.a_u32 as *const u32 as usize);
+        assert_eq!(err.found, &other.a_u32 as *const u32 as usize);
+
+        let mut a = Astruct2::linked_box(1, 2);
+        a.op_a_u8 = Some(NonNull::from(&other.a_u8));
+        assert_eq!((a.linked_a_u8(), a.linked_a_u32()), (None, Some(2)));
+        assert_eq!(a.validate().unwrap_err().field, "op_a_u8");
+    }
+
+    fn assert_rel_linked(a: &AstructRel, a_u32: u32) {
+        assert_eq!(a.linked_value(), Some(a_u32));
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn astruct_rel_moves_through_stack_vec_and_box() {
+        // Returned by value from new_linked
+        let a = AstructRel::new_linked(1, 10);
+        assert_rel_linked(&a, 10);
+        assert_eq!(a.a_u8, 1);
+
+        // Moved into another stack slot
+        let moved = a;
+        assert_rel_linked(&moved, 10);
+
+        // Pushed into a Vec that reallocates as it grows
+        let mut v = Vec::with_capacity(1);
+        v.push(moved);
+        let first = v.as_ptr();
+        for i in 0..64 {
+            v.push(AstructRel::new_linked(0, i));
+        }
+        assert!(!ptr::eq(first, v.as_ptr()));
+        assert_rel_linked(&v[0], 10);
+        for (i, a) in v[1..].iter().enumerate() {
+            assert_rel_linked(a, i as u32);
+        }
+
+        // Out of the Vec and into a Box
+        let boxed = Box::new(v.swap_remove(0));
+        assert_rel_linked(&boxed, 10);
+        let back = *boxed;
+        assert_rel_linked(&back, 10);
+    }
+
+    #[test]
+    fn astruct_rel_copy_links_to_itself() {
+        let a = AstructRel::new_linked(1, 2);
+        let mut b = a;
+        b.a_u32 = 3;
+        assert_rel_linked(&a, 2);
+        assert_rel_linked(&b, 3);
+    }
+
+    #[test]
+    fn drop_runs_once_for_pinned_box() {
+        let drops = Rc::new(Cell::new(0));
+        let a = Astruct::boxed_linked(1, DropCount(drops.clone()));
+        assert!(a.is_self_consistent());
+        drop(a);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn drop_of_stale_link_does_not_dereference() {
+        let drops = Rc::new(Cell::new(0));
+        // The moved-out value keeps the address of the freed box
+        let moved = *Astruct::linked_box(1, DropCount(drops.clone()));
+        assert!(moved.link_ptr().is_some());
+        assert!(moved.referenced_u32_ref().is_none());
+        drop(moved);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn link_and_unlink_pinned_box() {
+        let mut a = Box::into_pin(Astruct::boxed_unlinked(1, 2));
+        assert_eq!(a.referenced_u32(), None);
+        a.as_mut().link();
+        assert_eq!(a.validate(), Ok(()));
+        a.as_mut().set_a_u32(3);
+        assert_eq!(a.as_ref().referenced_u32_ref(), Some(&3));
+        a.as_mut().unlink();
+        assert_eq!(a.link_ptr(), None);
+        a.as_mut().set_a_u32(4);
+        assert_eq!(a.referenced_u32(), None);
+    }
+
+    #[test]
+    fn link_repairs_a_clone() {
+        let a = Astruct::boxed_linked(1, 2);
+        let mut c = Box::pin(Astruct::clone(&a));
+        assert_eq!(c.referenced_u32(), None);
+        c.as_mut().link();
+        c.as_mut().set_a_u32(5);
+        assert_eq!(c.referenced_u32(), Some(5));
+        assert_eq!(a.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn clone_is_unlinked() {
+        let a = Astruct::boxed_linked(1, 2);
+        let c = Astruct::clone(&a);
+        assert_eq!((c.a_u8, c.a_u32, c.link_ptr()), (1, 2, None));
+    }
+
+    #[test]
+    fn clone_boxed_outlives_original() {
+        let a = Astruct::boxed_linked(1, 2);
+        let c = a.clone_boxed();
+        drop(a);
+        assert!(ptr::eq(c.link_ptr().unwrap().as_ptr(), &c.a_u32));
+        assert_eq!(c.referenced_u32(), Some(2));
+        assert_eq!(c.validate(), Ok(()));
+    }
+
+    #[test]
+    fn clone_boxed_of_unlinked_stays_unlinked() {
+        let c = Astruct::boxed_unlinked(1, 2).clone_boxed();
+        assert_eq!(c.link_ptr(), None);
+    }
+
+    #[test]
+    fn referenced_u32_matches_a_u32() {
+        let a = crate::AstructBuilder::new()
+            .set_a_u8(3)
+            .set_a_u32(33)
+            .link()
+            .build();
+        assert_eq!(a.referenced_u32(), Some(a.a_u32));
+        assert_eq!(a.as_ref().referenced_u32(), Some(33));
+        assert!(ptr::eq(a.as_ref().referenced_u32_ref().unwrap(), &a.a_u32));
+        assert_eq!(Astruct::new(1, 2).referenced_u32_ref(), None);
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Point {
+        x: i16,
+        y: i16,
+    }
+
+    #[test]
+    fn generic_over_u64() {
+        let mut a = Astruct::boxed_linked(1, u64::MAX);
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!(a.referenced_u32(), Some(u64::MAX));
+        a.as_mut().set_a_u32(7);
+        assert_eq!(a.referenced_u32(), Some(7));
+    }
+
+    #[test]
+    fn generic_over_copy_struct() {
+        let p = Point { x: 1, y: -1 };
+        let a = crate::AstructBuilder::new().set_a_u32(p).link().build();
+        assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!(a.referenced_u32(), Some(p));
+        assert_eq!(a.validate(), Ok(()));
+        let c = a.clone_boxed();
+        assert!(ptr::eq(c.link_ptr().unwrap().as_ptr(), &c.a_u32));
+    }
+
+    #[test]
+    fn generic_payload_needs_no_default() {
+        struct NoDefault(u8);
+        let a = crate::AstructBuilder::new()
+            .set_a_u32(NoDefault(3))
+            .link()
+            .build();
+        assert_eq!(a.referenced_u32_ref().map(|v| v.0), Some(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not point at its own f")]
+    fn xstruct_moved_out_of_its_box_panics_in_p() {
+        let x = core::pin::pin!(*Xstruct::boxed(1));
+        x.as_ref().p();
+    }
+
+    #[test]
+    fn xstruct_clone_relinked_is_independent() {
+        let x = Xstruct::new_boxed(5);
+        assert!(ptr::eq(x.p.as_ptr(), &x.f));
+        let c = x.as_ref().clone_relinked();
+        assert!(ptr::eq(c.p.as_ptr(), &c.f));
+        assert!(!ptr::eq(c.p.as_ptr(), &x.f));
+        assert!(!ptr::eq(&*c, &*x));
+        drop(x);
+        assert_eq!(*c.as_ref().p(), 5);
+        assert_eq!(c.validate(), Ok(()));
+    }
+
+    #[test]
+    fn self_consistent_only_when_linked_here() {
+        assert!(Astruct::boxed_linked(1, 2).is_self_consistent());
+        assert!(!Astruct::boxed_unlinked(1, 2).is_self_consistent());
+
+        let mut miswired = Astruct::boxed_unlinked(1, 2);
+        let other = 3u32;
+        miswired.op_a_u32 = Some(NonNull::from(&other));
+        assert!(!miswired.is_self_consistent());
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "not self consistent"))]
+    fn debug_assert_self_consistent_panics_when_miswired() {
+        Astruct::boxed_unlinked(1, 2u32).debug_assert_self_consistent();
+    }
+
+    #[test]
+    fn astruct_offsets_follow_repr_c() {
+        const OFFSETS: AstructOffsets = astruct_offsets();
+        assert_eq!(OFFSETS.a_u8, 0);
+        // a_u8 is followed by padding up to a_u32's alignment
+        assert_eq!(OFFSETS.a_u32, align_of::<u32>());
+        assert_eq!(OFFSETS.op_a_u32 % align_of::<usize>(), 0);
+        assert!(OFFSETS.op_a_u32 >= OFFSETS.a_u32 + size_of::<u32>());
+
+        let a = Astruct::boxed_linked(1, 2u32);
+        let base = &*a as *const Astruct as usize;
+        assert_eq!(&a.a_u32 as *const u32 as usize - base, OFFSETS.a_u32);
+    }
+
+    #[test]
+    fn xstruct_project_borrows_fields() {
+        let mut x = Xstruct::new_boxed(3);
+        let proj = x.as_mut().project();
+        let p: Pin<&mut NonNull<u32>> = proj.p;
+        // Only compared, reading through p while f is borrowed mutably
+        // would alias it
+        assert!(ptr::eq(p.as_ptr(), &*proj.f));
+        assert_eq!(*proj.f, 3);
+        assert_eq!(*x.as_ref().p(), 3);
+        assert_eq!(x.validate(), Ok(()));
+    }
+
+    #[test]
+    fn a_u8_mut_keeps_the_link() {
+        let mut a = Astruct::boxed_linked(1, 2u32);
+        *a.as_mut().a_u8_mut() = 9;
+        *a.as_mut().a_u8_mut() += 1;
+        assert_eq!(a.a_u8, 10);
+        assert!(ptr::eq(a.as_ref().a_u32(), a.link_ptr().unwrap().as_ptr()));
+        assert_eq!(a.referenced_u32(), Some(2));
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn layout_matches_real_addresses() {
+        let layout = Astruct::layout();
+        assert_eq!(layout.size, size_of::<Astruct>());
+        assert_eq!(layout.align, align_of::<Astruct>());
+        let a = Astruct::boxed_linked(1, 2u32);
+        let base = &*a as *const Astruct as usize;
+        assert_eq!(&a.a_u8 as *const u8 as usize - base, layout.offsets.a_u8);
+        assert_eq!(&a.a_u32 as *const u32 as usize - base, layout.offsets.a_u32);
+        let link = ptr::addr_of!(a.op_a_u32) as usize;
+        assert_eq!(link - base, layout.offsets.op_a_u32);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn layout_on_64_bit() {
+        let layout = Astruct::layout();
+        assert_eq!((layout.size, layout.align), (16, 8));
+        assert_eq!(
+            layout.offsets,
+            AstructOffsets {
+                a_u8: 0,
+                a_u32: 4,
+                op_a_u32: 8
+            }
+        );
+        assert_eq!(
+            layout.to_string(),
+            "Astruct size=16 align=8\n  a_u8     @ 0..1\n  a_u32    @ 4..8\n  op_a_u32 @ 8..16"
+        );
+    }
+
+    #[test]
+    fn repr_c_bytes_round_trip() {
+        let a = Astruct::boxed_linked(7, 0x1234_5678);
+        let bytes = a.to_repr_c_bytes();
+        let b = Astruct::from_repr_c_bytes(&bytes).unwrap();
+        assert_eq!((b.a_u8, b.a_u32), (7, 0x1234_5678));
+        assert!(ptr::eq(b.link_ptr().unwrap().as_ptr(), &b.a_u32));
+    }
+
+    #[test]
+    fn to_repr_c_bytes_layout() {
+        let a = Astruct::boxed_linked(0xab, 0x0102_0304);
+        let bytes = a.to_repr_c_bytes();
+        assert_eq!(bytes.len(), size_of::<Astruct>());
+        assert_eq!(bytes[offset_of!(Astruct, a_u8)], 0xab);
+        let at = offset_of!(Astruct, a_u32);
+        assert_eq!(bytes[at..at + 4], 0x0102_0304u32.to_ne_bytes());
+        // The link is set but never serialized
+        assert!(a.link_ptr().is_some());
+        let at = offset_of!(Astruct, op_a_u32);
+        assert!(bytes[at..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn repr_c_bytes_allows_trailing_data() {
+        let mut bytes = Astruct::new(1, 2u32).to_repr_c_bytes();
+        bytes.extend_from_slice(&[0xff; 3]);
+        let a = Astruct::from_repr_c_bytes(&bytes).unwrap();
+        assert_eq!(a.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn stable_bytes_ignore_the_link() {
+        let a = Astruct::boxed_linked(1, 0x0403_0201);
+        let b = Astruct::from_record(a.to_record());
+        assert_ne!(a.link_ptr(), b.link_ptr());
+        assert_eq!(a.stable_bytes(), [1, 1, 2, 3, 4]);
+        assert_eq!(a.stable_bytes(), b.stable_bytes());
+        assert_eq!(
+            Astruct::new(1, 0x0403_0201).stable_bytes(),
+            a.stable_bytes()
+        );
+    }
+
+    #[test]
+    fn eq_and_hash_ignore_the_link_address() {
+        let a = Astruct::boxed_linked(1, 2);
+        let b = Astruct::boxed_linked(1, 2);
+        assert_ne!(a.link_ptr(), b.link_ptr());
+        assert_eq!(a, b);
+        let mut set = std::collections::HashSet::new();
+        set.insert(a);
+        set.insert(b);
+        assert_eq!(set.len(), 1);
+
+        // Linked and unlinked differ, as do different values
+        let unlinked = Box::into_pin(Astruct::boxed_unlinked(1, 2));
+        assert!(set.insert(unlinked));
+        assert!(set.insert(Astruct::boxed_linked(1, 3)));
+        assert!(set.insert(Astruct::boxed_linked(2, 2)));
+        assert_eq!(set.len(), 4);
+    }
+
+    #[test]
+    fn repr_c_bytes_too_short() {
+        let bytes = [0u8; 5];
+        assert_eq!(
+            Astruct::from_repr_c_bytes(&bytes).unwrap_err(),
+            ParseError::TooShort {
+                needed: Astruct::REPR_C_SIZE,
+                found: 5
+            }
+        );
+    }
+
+    #[test]
+    fn try_init_vec_is_empty_and_usable() {
+        let mut v = try_init_vec().unwrap();
+        assert_eq!(v.len(), 0);
+        v.push(1);
+        v.push(2);
+        assert_eq!(*v, [1, 2]);
+    }
+
+    #[test]
+    fn try_init_boxed_reports_panic() {
+        let r = try_init_boxed::<Vec<u32>>(|| panic!("init failed"));
+        assert_eq!(r, Err(UninitError::InitPanicked));
+    }
+}
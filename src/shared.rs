@@ -0,0 +1,117 @@
+//! Reference counted `Astruct`s for when one owner isn't enough.
+//!
+//! An `Rc`/`Arc` allocation never moves while any clone is alive, so the
+//! link written into it at construction stays valid for every clone.
+
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr::{addr_of_mut, NonNull};
+
+use crate::repr_c_ordered_build;
+use crate::selfref::Astruct;
+
+macro_rules! shared_astruct {
+    ($(#[$attr:meta])* $name:ident, $rc:ident) => {
+        $(#[$attr])*
+        // Not wrapped in Pin so the counts stay reachable, nothing hands out
+        // `&mut Astruct` or moves it out.
+        pub struct $name<T = u32>($rc<Astruct<T>>);
+
+        impl<T> $name<T> {
+            /// Allocate with `new_uninit`, write the values then the link
+            /// pointing into the shared allocation, then `assume_init`.
+            pub fn new_linked(a_u8: u8, a_u32: T) -> Self {
+                let mut uninit = $rc::<Astruct<T>>::new_uninit();
+                $name(unsafe {
+                    // Safety: freshly allocated so this is the only handle,
+                    // every field is written before assume_init.
+                    let p = $rc::get_mut(&mut uninit).unwrap().as_mut_ptr();
+                    repr_c_ordered_build!(p => Astruct<T> { a_u8: a_u8, a_u32: a_u32 });
+                    let link = NonNull::new_unchecked(addr_of_mut!((*p).a_u32));
+                    addr_of_mut!((*p).op_a_u32).write(Some(link));
+                    uninit.assume_init()
+                })
+            }
+
+            pub fn as_pin(&self) -> Pin<&Astruct<T>> {
+                // Safety: the allocation is never moved out of or borrowed
+                // mutably while shared
+                unsafe { Pin::new_unchecked(&*self.0) }
+            }
+
+            /// Whether both share one allocation.
+            pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+                $rc::ptr_eq(&a.0, &b.0)
+            }
+
+            /// The number of clones sharing the allocation.
+            pub fn strong_count(this: &Self) -> usize {
+                $rc::strong_count(&this.0)
+            }
+        }
+
+        impl<T> Clone for $name<T> {
+            fn clone(&self) -> Self {
+                $name(self.0.clone())
+            }
+        }
+
+        impl<T> Deref for $name<T> {
+            type Target = Astruct<T>;
+
+            fn deref(&self) -> &Astruct<T> {
+                &self.0
+            }
+        }
+
+        impl<T: core::fmt::Debug> core::fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_tuple(stringify!($name)).field(&*self.0).finish()
+            }
+        }
+    };
+}
+
+shared_astruct!(
+    /// An `Astruct` shared through `Rc`, every clone reads the same linked
+    /// `a_u32`.
+    AstructShared,
+    Rc
+);
+
+shared_astruct!(
+    /// [`AstructShared`] using `Arc`.
+    AstructSharedSync,
+    Arc
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn rc_clones_share_the_link() {
+        let a = AstructShared::new_linked(1, 42u32);
+        let clones: Vec<_> = (0..8).map(|_| a.clone()).collect();
+        assert_eq!(AstructShared::strong_count(&a), 9);
+        for c in &clones {
+            assert!(AstructShared::ptr_eq(&a, c));
+            assert_eq!(c.referenced_u32(), Some(42));
+            assert!(c.validate().is_ok());
+        }
+        drop(a);
+        assert_eq!(clones[0].referenced_u32(), Some(42));
+    }
+
+    #[test]
+    fn arc_clones_share_the_link() {
+        let a = AstructSharedSync::new_linked(1, 7u32);
+        let b = a.clone();
+        assert!(AstructSharedSync::ptr_eq(&a, &b));
+        assert!(core::ptr::eq(b.link_ptr().unwrap().as_ptr(), &a.a_u32));
+        assert_eq!(b.as_pin().referenced_u32(), Some(7));
+    }
+}
@@ -0,0 +1,245 @@
+//! A partially initialized heap allocation that drops what it has written.
+//!
+//! If a panic happens between the first field write and `assume_init()`,
+//! a plain `Box<MaybeUninit<T>>` frees the memory but leaks every field
+//! already written. [`UninitBox`] records each write in a drop mask and
+//! its `Drop` runs `drop_in_place` for exactly those fields.
+
+use alloc::boxed::Box;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ptr;
+
+pub use expr_builder_pattern_derive::UninitDrop;
+
+/// Offset and drop glue of one field, as generated by `#[derive(UninitDrop)]`.
+pub struct FieldInfo {
+    pub offset: usize,
+    pub drop: unsafe fn(*mut u8),
+}
+
+/// Drop the `F` stored at `p`.
+///
+/// # Safety
+///
+/// `p` must point at an initialized, properly aligned `F`.
+pub unsafe fn drop_field<F>(p: *mut u8) {
+    ptr::drop_in_place(p.cast::<F>())
+}
+
+/// Per field drop glue of a struct, in declaration order.
+///
+/// # Safety
+///
+/// Every entry must describe a field of `Self`, use `#[derive(UninitDrop)]`.
+pub unsafe trait UninitDrop: Sized {
+    const FIELDS: &'static [FieldInfo];
+}
+
+/// The type and offset of field `IDX` of `Self`.
+///
+/// # Safety
+///
+/// `OFFSET` must be the offset of a field of type `Type`, use
+/// `#[derive(UninitDrop)]`.
+pub unsafe trait FieldAt<const IDX: usize> {
+    type Type;
+    const OFFSET: usize;
+}
+
+/// `Box<MaybeUninit<T>>` plus a mask of the fields written so far.
+pub struct UninitBox<T: UninitDrop> {
+    uninit: ManuallyDrop<Box<MaybeUninit<T>>>,
+    written: u64,
+}
+
+impl<T: UninitDrop> UninitBox<T> {
+    const ALL_WRITTEN: u64 = {
+        assert!(
+            T::FIELDS.len() <= 64,
+            "UninitBox supports at most 64 fields"
+        );
+        if T::FIELDS.len() == 64 {
+            u64::MAX
+        } else {
+            (1 << T::FIELDS.len()) - 1
+        }
+    };
+
+    pub fn new() -> Self {
+        UninitBox {
+            uninit: ManuallyDrop::new(Box::new_uninit()),
+            written: 0,
+        }
+    }
+
+    /// Write field `IDX` and mark it written before returning a reference
+    /// to it at its final address. Writing an already written field drops
+    /// the previous value first.
+    ///
+    /// # Safety
+    ///
+    /// `FieldAt` exists for private fields too, so this bypasses the
+    /// constructors of `T`. `val` must uphold every invariant `T` places
+    /// on the field once the value is complete, e.g. an `Astruct`'s
+    /// `op_a_u32` must be `None` or point at its own `a_u32`. Safe code
+    /// can't write one:
+    ///
+    /// ```compile_fail
+    /// use expr_builder_pattern::{Astruct, UninitBox};
+    ///
+    /// let mut ub = UninitBox::<Astruct>::new();
+    /// ub.write_field::<2, _>(None::<core::ptr::NonNull<u32>>);
+    /// ```
+    pub unsafe fn write_field<const IDX: usize, F>(&mut self, val: F) -> &mut F
+    where
+        T: FieldAt<IDX, Type = F>,
+    {
+        let bit = 1 << IDX;
+        // Safety: OFFSET is the offset of a field of type F inside the
+        // allocation, the mask bit tells whether it holds a value to drop
+        unsafe {
+            let p = self
+                .uninit
+                .as_mut_ptr()
+                .cast::<u8>()
+                .add(<T as FieldAt<IDX>>::OFFSET)
+                .cast::<F>();
+            if self.written & bit != 0 {
+                self.written &= !bit;
+                ptr::drop_in_place(p);
+            }
+            p.write(val);
+            self.written |= bit;
+            &mut *p
+        }
+    }
+
+    /// True once every field has been written.
+    pub fn is_complete(&self) -> bool {
+        self.written == Self::ALL_WRITTEN
+    }
+
+    /// The initialized box, or `self` back when fields are still missing.
+    pub fn try_assume_init(self) -> Result<Box<T>, Self> {
+        if !self.is_complete() {
+            return Err(self);
+        }
+        let mut this = ManuallyDrop::new(self);
+        // Safety: every field is written and `this` is never dropped.
+        unsafe { Ok(ManuallyDrop::take(&mut this.uninit).assume_init()) }
+    }
+}
+
+impl<T: UninitDrop> Default for UninitBox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: UninitDrop> Drop for UninitBox<T> {
+    fn drop(&mut self) {
+        let base = self.uninit.as_mut_ptr().cast::<u8>();
+        for (i, field) in T::FIELDS.iter().enumerate() {
+            if self.written & (1 << i) != 0 {
+                // Safety: the mask bit is only set after the field is written.
+                unsafe { (field.drop)(base.add(field.offset)) }
+            }
+        }
+        // Safety: not used again, frees the allocation without dropping T.
+        unsafe { ManuallyDrop::drop(&mut self.uninit) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selfref::Astruct;
+    use core::ptr::NonNull;
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    struct Counted(Rc<Cell<u32>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn failing_counted() -> Counted {
+        panic!("failed to produce second")
+    }
+
+    #[derive(UninitDrop)]
+    struct Pair {
+        first: Counted,
+        second: Counted,
+    }
+
+    #[test]
+    fn astruct_field_by_field() {
+        let mut ub = UninitBox::<Astruct>::new();
+        // Safety: the link points at a_u32 in the same allocation
+        unsafe {
+            ub.write_field::<0, _>(1u8);
+            let a_u32 = NonNull::from(ub.write_field::<1, _>(2u32));
+            assert!(!ub.is_complete());
+            ub.write_field::<2, _>(Some(a_u32));
+            ub.write_field::<3, _>(core::marker::PhantomPinned);
+        }
+        let a = Box::into_pin(ub.try_assume_init().ok().unwrap());
+        assert_eq!(a.a_u8, 1);
+        assert_eq!(a.referenced_u32(), Some(2));
+    }
+
+    #[test]
+    fn incomplete_box_is_handed_back() {
+        let mut ub = UninitBox::<Astruct>::new();
+        // Safety: a_u8 has no invariant
+        unsafe { ub.write_field::<0, _>(1u8) };
+        assert!(ub.try_assume_init().is_err());
+    }
+
+    #[test]
+    fn drops_only_written_fields() {
+        let drops = Rc::new(Cell::new(0));
+        let mut ub = UninitBox::<Pair>::new();
+        // Safety: Pair has no invariants
+        unsafe { ub.write_field::<0, _>(Counted(drops.clone())) };
+        drop(ub);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn panic_during_init_drops_written_fields() {
+        let drops = Rc::new(Cell::new(0));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut ub = UninitBox::<Pair>::new();
+            // Safety: Pair has no invariants
+            unsafe {
+                ub.write_field::<0, _>(Counted(drops.clone()));
+                ub.write_field::<1, _>(failing_counted());
+            }
+        }));
+        assert!(result.is_err());
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn rewriting_a_field_drops_the_old_value() {
+        let drops = Rc::new(Cell::new(0));
+        let mut ub = UninitBox::<Pair>::new();
+        // Safety: Pair has no invariants
+        unsafe {
+            ub.write_field::<0, _>(Counted(drops.clone()));
+            ub.write_field::<0, _>(Counted(drops.clone()));
+            assert_eq!(drops.get(), 1);
+            ub.write_field::<1, _>(Counted(drops.clone()));
+        }
+        let pair = ub.try_assume_init().ok().unwrap();
+        assert_eq!(drops.get(), 1);
+        drop(pair);
+        assert_eq!(drops.get(), 3);
+    }
+}
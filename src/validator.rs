@@ -0,0 +1,127 @@
+//! Post-build checks on the values produced by the builders.
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+use core::mem::size_of;
+
+use crate::selfref::{Astruct, Xstruct};
+
+/// Checks a built value before it is handed out.
+pub trait Validator<T> {
+    fn validate(&self, value: &T) -> Result<(), ValidationError>;
+}
+
+impl<T, F: Fn(&T) -> Result<(), ValidationError>> Validator<T> for F {
+    fn validate(&self, value: &T) -> Result<(), ValidationError> {
+        self(value)
+    }
+}
+
+/// The field that failed validation and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    pub field_name: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field_name, self.message)
+    }
+}
+
+impl core::error::Error for ValidationError {}
+
+/// Verifies that every self reference points inside the value's own
+/// `size_of::<T>()` bytes rather than at some unrelated address.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SelfRefValidator;
+
+impl SelfRefValidator {
+    fn check_within<T, U>(
+        value: &T,
+        field_name: &'static str,
+        target: *const U,
+    ) -> Result<(), ValidationError> {
+        let start = value as *const T as usize;
+        let end = start + size_of::<T>();
+        let addr = target as usize;
+        if addr >= start && addr + size_of::<U>() <= end {
+            Ok(())
+        } else {
+            Err(ValidationError {
+                field_name,
+                message: format!(
+                    "points to 0x{:x}, outside of 0x{:x}..0x{:x}",
+                    addr, start, end
+                ),
+            })
+        }
+    }
+}
+
+impl<T> Validator<Astruct<T>> for SelfRefValidator {
+    /// An unlinked `op_a_u32` has nothing to check and passes.
+    fn validate(&self, value: &Astruct<T>) -> Result<(), ValidationError> {
+        match value.link_ptr() {
+            Some(p) => Self::check_within(value, "op_a_u32", p.as_ptr()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Validator<Xstruct> for SelfRefValidator {
+    fn validate(&self, value: &Xstruct) -> Result<(), ValidationError> {
+        Self::check_within(value, "p", value.link_ptr().as_ptr())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr::NonNull;
+
+    #[test]
+    fn linked_astruct_passes() {
+        let a = Astruct::boxed_linked(1, 2);
+        assert_eq!(SelfRefValidator.validate(&*a), Ok(()));
+    }
+
+    #[test]
+    fn unlinked_astruct_passes() {
+        let a = Astruct::boxed_unlinked(1, 2);
+        assert_eq!(SelfRefValidator.validate(&*a), Ok(()));
+    }
+
+    #[test]
+    fn link_to_other_value_fails() {
+        let other = 7u32;
+        let mut a = Astruct::boxed_unlinked(1, 2);
+        a.op_a_u32 = Some(NonNull::from(&other));
+        let err = SelfRefValidator.validate(&*a).unwrap_err();
+        assert_eq!(err.field_name, "op_a_u32");
+    }
+
+    #[test]
+    fn xstruct_passes() {
+        let x = Xstruct::boxed(3);
+        assert_eq!(SelfRefValidator.validate(&*x), Ok(()));
+    }
+
+    #[test]
+    fn closures_are_validators() {
+        let v = |a: &Astruct| {
+            if a.a_u8 == 0 {
+                Err(ValidationError {
+                    field_name: "a_u8",
+                    message: "must not be zero".into(),
+                })
+            } else {
+                Ok(())
+            }
+        };
+        assert!(v.validate(&Astruct::new(0, 1)).is_err());
+        assert!(v.validate(&Astruct::new(1, 1)).is_ok());
+    }
+}
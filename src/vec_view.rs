@@ -0,0 +1,128 @@
+//! A `Vec` together with a link to one of its elements.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ptr::NonNull;
+
+/// Owns `data` and keeps `link` pointing at `data[index]`.
+///
+/// The link targets the `Vec`'s heap buffer, so moving a `VecView` is fine
+/// but reallocating the buffer isn't. `data` is therefore only exposed as
+/// `&[u32]` and [`push`](VecView::push), the one mutating call, relinks
+/// afterwards.
+pub struct VecView {
+    data: Vec<u32>,
+    index: usize,
+    link: NonNull<u32>,
+}
+
+// Safety: link points into the Vec owned alongside it, so this is as
+// thread safe as the Vec.
+unsafe impl Send for VecView {}
+unsafe impl Sync for VecView {}
+
+impl VecView {
+    /// Link to `data[index]`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds for `data`.
+    pub fn new(data: Vec<u32>, index: usize) -> Self {
+        assert!(
+            index < data.len(),
+            "index {} out of bounds for {} elements",
+            index,
+            data.len()
+        );
+        let mut v = VecView {
+            data,
+            index,
+            link: NonNull::dangling(),
+        };
+        v.relink();
+        v
+    }
+
+    /// The element the link points at.
+    pub fn get_linked(&self) -> u32 {
+        // Safety: relinked after every change to the buffer, and index is
+        // in bounds as elements are never removed.
+        unsafe { *self.link.as_ptr() }
+    }
+
+    /// Append `v`, relinking in case the buffer was reallocated.
+    pub fn push(&mut self, v: u32) {
+        self.data.push(v);
+        self.relink();
+    }
+
+    pub fn data(&self) -> &[u32] {
+        &self.data
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The raw link, for inspecting where it points.
+    pub fn link_ptr(&self) -> NonNull<u32> {
+        self.link
+    }
+
+    fn relink(&mut self) {
+        self.link = NonNull::from(&mut self.data[self.index]);
+    }
+}
+
+impl fmt::Debug for VecView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VecView")
+            .field("data", &self.data)
+            .field("index", &self.index)
+            .field("linked", &self.get_linked())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use core::ptr;
+
+    #[test]
+    fn links_to_index() {
+        let v = VecView::new(vec![1, 2, 3], 1);
+        assert_eq!(v.get_linked(), 2);
+        assert!(ptr::eq(v.link_ptr().as_ptr(), &v.data()[1]));
+    }
+
+    #[test]
+    fn push_past_capacity_relinks() {
+        let mut data = Vec::with_capacity(2);
+        data.extend([10, 20]);
+        let mut v = VecView::new(data, 0);
+        let before = v.data().as_ptr();
+        for i in 0..64 {
+            v.push(i);
+        }
+        assert!(!ptr::eq(before, v.data().as_ptr()));
+        assert_eq!(v.get_linked(), 10);
+        assert!(ptr::eq(v.link_ptr().as_ptr(), &v.data()[0]));
+        assert_eq!(v.data().len(), 66);
+    }
+
+    #[test]
+    fn moving_keeps_the_link() {
+        let v = VecView::new(vec![4, 5], 1);
+        let boxed = alloc::boxed::Box::new(v);
+        assert_eq!(boxed.get_linked(), 5);
+        assert!(ptr::eq(boxed.link_ptr().as_ptr(), &boxed.data()[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn index_out_of_bounds_panics() {
+        VecView::new(vec![1], 1);
+    }
+}
@@ -0,0 +1,76 @@
+//! Types that may be created from zeroed memory.
+
+use alloc::boxed::Box;
+use core::marker::{PhantomData, PhantomPinned};
+use core::ptr::NonNull;
+
+/// An all zero bit pattern is a valid value of the type.
+///
+/// # Safety
+///
+/// Implement it only if every field is itself `Zeroable`, or otherwise
+/// valid when zeroed. References and `NonNull` never are, which is why
+/// `Xstruct`, whose `p` is a `NonNull<u32>`, doesn't implement it:
+///
+/// ```compile_fail
+/// use expr_builder_pattern::{zeroable::boxed_zeroed, Xstruct};
+///
+/// let x = boxed_zeroed::<Xstruct>();
+/// ```
+pub unsafe trait Zeroable {}
+
+macro_rules! zeroable {
+    ($($ty:ty),*) => {
+        $(unsafe impl Zeroable for $ty {})*
+    };
+}
+
+zeroable!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    bool,
+    ()
+);
+
+// Safety: the null niche makes zero `None`
+unsafe impl<T: ?Sized> Zeroable for Option<NonNull<T>> {}
+unsafe impl<T: ?Sized> Zeroable for PhantomData<T> {}
+unsafe impl Zeroable for PhantomPinned {}
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+
+/// Box a `T` from zeroed memory without running any constructor.
+pub fn boxed_zeroed<T: Zeroable>() -> Box<T> {
+    // Safety: zero is a valid T
+    unsafe { Box::<T>::new_zeroed().assume_init() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selfref::Astruct;
+
+    #[test]
+    fn astruct_fields_are_zero() {
+        let z = boxed_zeroed::<Astruct<u64>>();
+        assert_eq!((z.a_u8, z.a_u32), (0, 0));
+        assert_eq!(z.link_ptr(), None);
+        assert_eq!(z.referenced_u32(), None);
+        assert!(z.validate().is_ok());
+    }
+
+    #[test]
+    fn arrays_and_integers() {
+        assert_eq!(*boxed_zeroed::<[u32; 4]>(), [0; 4]);
+        assert!(!*boxed_zeroed::<bool>());
+    }
+}
@@ -0,0 +1,68 @@
+//! `try_build_astruct` against an allocator that can be told to fail.
+//!
+//! The global allocator is per binary, so this lives in its own test file.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::ptr;
+
+use expr_builder_pattern::{try_build_astruct, AllocError, Astruct};
+
+thread_local! {
+    // Only this thread's allocations fail, the harness keeps working
+    static FAIL: Cell<bool> = const { Cell::new(false) };
+}
+
+struct FailingAlloc;
+
+unsafe impl GlobalAlloc for FailingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if FAIL.with(Cell::get) {
+            ptr::null_mut()
+        } else {
+            System.alloc(layout)
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: FailingAlloc = FailingAlloc;
+
+fn failing<R>(f: impl FnOnce() -> R) -> R {
+    FAIL.with(|fail| fail.set(true));
+    let r = f();
+    FAIL.with(|fail| fail.set(false));
+    r
+}
+
+#[test]
+fn out_of_memory_is_an_error() {
+    let err = failing(|| try_build_astruct(1, 2).map(drop)).unwrap_err();
+    let layout = Layout::new::<Astruct>();
+    assert_eq!(
+        err,
+        AllocError {
+            size: layout.size(),
+            align: layout.align(),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "allocating {} bytes aligned to {} failed",
+            layout.size(),
+            layout.align()
+        )
+    );
+}
+
+#[test]
+fn successful_allocation_is_linked() {
+    let a = try_build_astruct(1, 2).unwrap();
+    assert_eq!((a.a_u8, a.a_u32), (1, 2));
+    assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+}
@@ -0,0 +1,24 @@
+use std::ptr;
+
+use expr_builder_pattern::const_build::new_astruct;
+use expr_builder_pattern::Astruct;
+
+const MY_CONFIG: Astruct = new_astruct(1, 42);
+static GLOBAL: Astruct = new_astruct(3, 9);
+
+#[test]
+fn const_values_are_unlinked() {
+    assert_eq!((MY_CONFIG.a_u8, MY_CONFIG.a_u32), (1, 42));
+    assert_eq!(MY_CONFIG.link_ptr(), None);
+    assert_eq!(MY_CONFIG.validate(), Ok(()));
+    assert_eq!(new_astruct(0, 0).validate(), Ok(()));
+    assert_eq!((GLOBAL.a_u8, GLOBAL.link_ptr()), (3, None));
+}
+
+#[test]
+fn const_value_links_once_boxed() {
+    let mut a = Box::pin(MY_CONFIG);
+    a.as_mut().link();
+    assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+    assert_eq!(a.referenced_u32(), Some(42));
+}
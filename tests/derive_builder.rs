@@ -0,0 +1,326 @@
+use std::marker::{PhantomData, PhantomPinned};
+use std::ptr::{self, NonNull};
+
+use expr_builder_pattern::{BuildError, Builder, EnumBuilder, Mutable, RoundDown, ValidationError};
+
+// Mirrors the crate's Astruct
+#[derive(Builder)]
+#[repr(C)]
+struct Astruct {
+    a_u8: u8,
+    a_u32: u32,
+    #[builder(self_ref(target = "a_u32"))]
+    op_a_u32: Option<NonNull<u32>>,
+}
+
+// Same as Astruct with the shorthand attribute. The test reads through
+// the link, which Miri only accepts if the boxed value is !Unpin
+#[derive(Builder)]
+#[repr(C)]
+struct PointsTo {
+    a_u32: u32,
+    #[self_ref(points_to = "a_u32")]
+    op_a_u32: *const u32,
+    _pin: PhantomPinned,
+}
+
+// The link can be set directly or computed once a_u32 is in place
+#[derive(Builder)]
+#[repr(C)]
+struct LazyAstruct {
+    a_u8: u8,
+    a_u32: u32,
+    #[builder(lazy_default = "self_ref")]
+    op_a_u32: Option<NonNull<u32>>,
+}
+
+fn link_a_u32(p: *const LazyAstruct) -> Option<NonNull<u32>> {
+    NonNull::new(unsafe { ptr::addr_of!((*p).a_u32) }.cast_mut())
+}
+
+// Link types spelled out with their full paths
+#[derive(Builder)]
+struct FullPaths {
+    a_u8: u8,
+    a_u32: u32,
+    #[builder(self_ref(target = "a_u32"))]
+    op_a_u32: Option<core::ptr::NonNull<u32>>,
+    #[builder(self_ref(target = "a_u8"))]
+    op_a_u8: ::core::option::Option<::std::ptr::NonNull<u8>>,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+struct Plain<T> {
+    name: String,
+    value: T,
+    #[builder(optional)]
+    count: u32,
+    _marker: PhantomData<T>,
+}
+
+// Setters normalize through a function or a FieldMapper type
+#[derive(Builder)]
+struct Mapped {
+    #[builder(map = clamp_u8)]
+    a_u8: u8,
+    #[builder(map = RoundDown::<4>)]
+    a_u32: u32,
+}
+
+fn clamp_u8(v: u8) -> u8 {
+    v.min(100)
+}
+
+// try_set_version checks the value, set_version still stores anything
+#[derive(Builder, Debug)]
+struct Versioned {
+    #[builder(validate = validate_version)]
+    version: u8,
+}
+
+fn validate_version(v: u8) -> Result<(), &'static str> {
+    if v == 0 {
+        Err("0 is reserved for no version")
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(EnumBuilder, Debug, PartialEq)]
+enum Shape<T> {
+    Empty,
+    Point(T),
+    NamedPoint((String, T)),
+}
+
+#[test]
+fn builds_linked_astruct() {
+    let a = AstructBuilder::new()
+        .set_a_u8(1)
+        .set_a_u32(123)
+        .build()
+        .unwrap();
+    assert_eq!(a.a_u8, 1);
+    assert_eq!(a.a_u32, 123);
+    assert!(ptr::eq(a.op_a_u32.unwrap().as_ptr(), &a.a_u32));
+}
+
+#[test]
+fn points_to_shorthand_links() {
+    let a = PointsToBuilder::new().set_a_u32(5).build().unwrap();
+    assert!(ptr::eq(a.op_a_u32, &a.a_u32));
+    assert_eq!(unsafe { *a.op_a_u32 }, 5);
+}
+
+#[test]
+fn full_paths_are_accepted_as_links() {
+    let a = FullPathsBuilder::new()
+        .set_a_u8(1)
+        .set_a_u32(2)
+        .build()
+        .unwrap();
+    assert!(ptr::eq(a.op_a_u32.unwrap().as_ptr(), &a.a_u32));
+    assert!(ptr::eq(a.op_a_u8.unwrap().as_ptr(), &a.a_u8));
+}
+
+#[test]
+fn lazy_default_links_after_value_fields() {
+    let a = LazyAstructBuilder::new()
+        .set_a_u8(1)
+        .set_a_u32(2)
+        .set_op_a_u32_lazy(link_a_u32)
+        .build()
+        .unwrap();
+    assert_eq!((a.a_u8, a.a_u32), (1, 2));
+    assert!(ptr::eq(a.op_a_u32.unwrap().as_ptr(), &a.a_u32));
+}
+
+#[test]
+fn lazy_default_is_skipped_when_set() {
+    let a = LazyAstructBuilder::new()
+        .set_a_u8(1)
+        .set_a_u32(2)
+        .set_op_a_u32(None)
+        .set_op_a_u32_lazy(link_a_u32)
+        .build()
+        .unwrap();
+    assert_eq!(a.op_a_u32, None);
+}
+
+#[test]
+fn lazy_field_without_value_or_default_is_missing() {
+    let err = LazyAstructBuilder::new()
+        .set_a_u8(1)
+        .set_a_u32(2)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(err, BuildError::MissingField { name: "op_a_u32" });
+}
+
+#[test]
+fn reset_clears_values_and_lazy_defaults() {
+    let mut b = LazyAstructBuilder::<Mutable>::default();
+    b.set_a_u8(1).set_a_u32(2).set_op_a_u32_lazy(link_a_u32);
+    b.reset();
+    b.set_a_u8(3).set_a_u32(4);
+    let err = b.build().err().unwrap();
+    assert_eq!(err, BuildError::MissingField { name: "op_a_u32" });
+}
+
+#[test]
+fn missing_field_is_reported() {
+    let err = AstructBuilder::new().set_a_u8(1).build().err().unwrap();
+    assert_eq!(err, BuildError::MissingField { name: "a_u32" });
+    assert_eq!(err.to_string(), "missing field `a_u32`");
+}
+
+#[test]
+fn optional_field_defaults() {
+    let p = PlainBuilder::new()
+        .set_name("x".to_string())
+        .set_value(1.5f32)
+        .build()
+        .unwrap();
+    assert_eq!(
+        p,
+        Plain {
+            name: "x".to_string(),
+            value: 1.5,
+            count: 0,
+            _marker: PhantomData,
+        }
+    );
+}
+
+#[test]
+fn setters_overwrite() {
+    let p = PlainBuilder::new()
+        .set_name("x".to_string())
+        .set_value(1u8)
+        .set_count(2)
+        .set_count(3)
+        .build()
+        .unwrap();
+    assert_eq!(p.count, 3);
+}
+
+#[test]
+fn collecting_errors_reports_every_missing_field() {
+    let errs = PlainBuilder::<u8>::new()
+        .build_collecting_errors()
+        .unwrap_err();
+    assert_eq!(
+        errs,
+        [
+            BuildError::MissingField { name: "name" },
+            BuildError::MissingField { name: "value" },
+        ]
+    );
+    let errs = AstructBuilder::new()
+        .build_collecting_errors()
+        .err()
+        .unwrap();
+    assert_eq!(
+        errs,
+        [
+            BuildError::MissingField { name: "a_u8" },
+            BuildError::MissingField { name: "a_u32" },
+        ]
+    );
+}
+
+#[test]
+fn collecting_errors_builds_when_complete() {
+    let a = AstructBuilder::new()
+        .set_a_u8(1)
+        .set_a_u32(2)
+        .build_collecting_errors()
+        .unwrap();
+    assert!(ptr::eq(a.op_a_u32.unwrap().as_ptr(), &a.a_u32));
+}
+
+#[test]
+fn map_applies_in_both_modes() {
+    let m = MappedBuilder::new()
+        .set_a_u8(200)
+        .set_a_u32(7)
+        .build()
+        .unwrap();
+    assert_eq!((m.a_u8, m.a_u32), (100, 4));
+    let mut b = MappedBuilder::<Mutable>::default();
+    b.set_a_u8(5).set_a_u32(9);
+    let m = b.build().unwrap();
+    assert_eq!((m.a_u8, m.a_u32), (5, 8));
+}
+
+#[test]
+fn validate_generates_try_setter() {
+    let err = VersionedBuilder::new().try_set_version(0).err().unwrap();
+    assert_eq!(
+        err,
+        ValidationError {
+            field_name: "version",
+            message: "0 is reserved for no version".to_string(),
+        }
+    );
+    let v = VersionedBuilder::new()
+        .try_set_version(2)
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(v.version, 2);
+
+    let mut b = VersionedBuilder::<Mutable>::default();
+    assert!(b.try_set_version(0).is_err());
+    b.set_version(0);
+    assert_eq!(b.build().unwrap().version, 0);
+}
+
+#[test]
+fn generic_missing_field() {
+    let err = PlainBuilder::<u8>::new().set_value(1).build().unwrap_err();
+    assert_eq!(err, BuildError::MissingField { name: "name" });
+}
+
+#[test]
+fn mutable_mode_sets_conditionally() {
+    let mut b = AstructBuilder::<Mutable>::default();
+    for i in 0..4u32 {
+        if i % 2 == 1 {
+            b.set_a_u32(i);
+        }
+    }
+    b.set_a_u8(9);
+    let a = b.build().unwrap();
+    assert_eq!((a.a_u8, a.a_u32), (9, 3));
+    assert!(ptr::eq(a.op_a_u32.unwrap().as_ptr(), &a.a_u32));
+}
+
+#[test]
+fn modes_convert_both_ways() {
+    let mut b = PlainBuilder::new().set_value(1u8).into_mutable();
+    b.set_name("m".to_string()).set_count(2);
+    let p = b.into_consuming().set_count(3).build().unwrap();
+    assert_eq!((p.name.as_str(), p.value, p.count), ("m", 1, 3));
+}
+
+#[test]
+fn enum_builder_selects_variants() {
+    assert_eq!(
+        ShapeBuilder::new().set_empty().build(),
+        Ok(Shape::<u8>::Empty)
+    );
+    assert_eq!(
+        ShapeBuilder::new().set_empty().set_point(1u8).build(),
+        Ok(Shape::Point(1))
+    );
+    let s = ShapeBuilder::new()
+        .set_named_point(("p".to_string(), 2u8))
+        .build();
+    assert_eq!(s, Ok(Shape::NamedPoint(("p".to_string(), 2))));
+    assert_eq!(
+        ShapeBuilder::<u8>::new().build(),
+        Err(BuildError::NoVariantSelected)
+    );
+}
@@ -0,0 +1,74 @@
+//! The safe construction paths, kept small enough to run under
+//! `cargo +nightly miri test --test miri` and meant to stay green there.
+
+use expr_builder_pattern::dyn_builder::DynBuilder;
+use expr_builder_pattern::{
+    stack_self_ref, try_build_astruct, Astruct, AstructArena, AstructBuilder, AstructRel,
+    InPlaceInit, SelfRef, SelfRefClone, SelfRefPin, Xstruct,
+};
+
+// Miri is slow, a few iterations are enough for it to see every path
+const N: u32 = if cfg!(miri) { 4 } else { 64 };
+
+#[test]
+fn builder_paths() {
+    let mut all = Vec::new();
+    for i in 0..N {
+        let a = AstructBuilder::new()
+            .set_a_u8(1)
+            .set_a_u32(i)
+            .link()
+            .build();
+        assert_eq!(a.referenced_u32(), Some(i));
+        all.push(AstructBuilder::new().set_a_u32(i).link().build_linked());
+    }
+    for (i, a) in all.iter().enumerate() {
+        assert_eq!(a.referenced_u32(), Some(i as u32));
+        assert_eq!(a.self_ref_clone().referenced_u32(), Some(i as u32));
+    }
+}
+
+#[test]
+fn self_ref_paths() {
+    let x = unsafe { SelfRef::<Xstruct>::build(|p| Xstruct::init_in_place(p, 47)) };
+    assert_eq!(*x.as_ref().p(), 47);
+
+    let x = unsafe { SelfRefPin::new_uninit(|p: *mut Xstruct| Xstruct::init_in_place(p, 48)) };
+    assert_eq!(*x.as_ref().p(), 48);
+
+    stack_self_ref!(let x = 49);
+    assert_eq!(*x.as_ref().p(), 49);
+}
+
+#[test]
+fn arena_and_rel_paths() {
+    let arena = AstructArena::new();
+    for i in 0..N {
+        assert_eq!(arena.alloc_astruct(1, i).referenced_u32(), Some(i));
+    }
+
+    let v: Vec<AstructRel> = (0..N).map(|i| AstructRel::new_linked(0, i)).collect();
+    for (i, a) in v.iter().enumerate() {
+        assert_eq!(a.linked_value(), Some(i as u32));
+    }
+}
+
+#[test]
+fn raw_allocation_paths() {
+    for i in 0..N {
+        let a = try_build_astruct(1, i).unwrap();
+        assert_eq!(a.referenced_u32(), Some(i));
+    }
+
+    let frame = DynBuilder::<u64>::new(8 + 8 * N as usize)
+        .unwrap()
+        .build(1, &[2; 3]);
+    assert_eq!(frame.header, 1);
+    assert_eq!(&frame.data[..4], &[2, 2, 2, 0]);
+
+    let init: Vec<(u8, u32)> = (0..N).map(|i| (1, i)).collect();
+    let slice = Astruct::boxed_slice_linked(&init);
+    for (i, a) in slice.iter().enumerate() {
+        assert_eq!(a.referenced_u32(), Some(i as u32));
+    }
+}
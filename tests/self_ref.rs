@@ -0,0 +1,33 @@
+use std::marker::PhantomPinned;
+use std::ptr::{self, addr_of, addr_of_mut};
+
+use expr_builder_pattern::SelfRefBuilder;
+
+// Mirrors the original Astruct, whose link was a plain reference. Without
+// `_pin` the box would be Unpin and Miri rejects using the link once it is
+// returned
+#[repr(C)]
+struct Linked<'m> {
+    a_u8: u8,
+    a_u32: u32,
+    op_a_u32: Option<&'m u32>,
+    _pin: PhantomPinned,
+}
+
+#[test]
+fn self_ref_builder_links_astruct_like_struct() {
+    let x = unsafe {
+        SelfRefBuilder::<Linked>::new().build(
+            |p| {
+                addr_of_mut!((*p).a_u8).write(4);
+                addr_of_mut!((*p).a_u32).write(456);
+                addr_of_mut!((*p)._pin).write(PhantomPinned);
+            },
+            |p| addr_of_mut!((*p).op_a_u32).write(Some(&*addr_of!((*p).a_u32))),
+        )
+    };
+
+    assert_eq!(x.a_u8, 4);
+    assert_eq!(x.a_u32, 456);
+    assert!(ptr::eq(x.op_a_u32.unwrap(), &x.a_u32));
+}
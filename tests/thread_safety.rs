@@ -0,0 +1,89 @@
+use std::cell::Cell;
+use std::pin::Pin;
+use std::ptr;
+use std::sync::MutexGuard;
+use std::thread;
+
+use expr_builder_pattern::enum_builder::{AstructKind, AstructKindBuilder};
+use expr_builder_pattern::{
+    Astruct, Astruct2, AstructBuilder, AstructRel, AstructSharedSync, Linked, OwnedSlice, VecView,
+    Xstruct,
+};
+
+fn assert_send_sync<T: Send + Sync>() {}
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn self_referential_types_are_send_and_sync() {
+    assert_send_sync::<Astruct>();
+    assert_send_sync::<Astruct<String>>();
+    assert_send_sync::<Astruct2>();
+    assert_send_sync::<AstructRel>();
+    assert_send_sync::<AstructSharedSync>();
+    assert_send_sync::<OwnedSlice>();
+    assert_send_sync::<VecView>();
+    assert_send_sync::<Xstruct>();
+}
+
+#[test]
+fn astruct_follows_its_value_type() {
+    // Cell is Send but not Sync, MutexGuard the other way round
+    assert_send::<Astruct<Cell<u32>>>();
+    assert_sync::<Astruct<MutexGuard<'static, u32>>>();
+    assert_send_sync::<Pin<Box<Astruct<String>>>>();
+}
+
+#[test]
+fn builder_outputs_are_send_and_sync() {
+    assert_send_sync::<AstructBuilder>();
+    assert_send_sync::<AstructBuilder<Linked, u32, AstructKindBuilder>>();
+    assert_send_sync::<AstructKind>();
+    assert_send_sync::<AstructKindBuilder>();
+}
+
+#[test]
+fn pinned_astruct_keeps_its_link_on_another_thread() {
+    let a = Astruct::boxed_linked(1, 42u32);
+    let addr = &a.a_u32 as *const u32 as usize;
+    let a = thread::spawn(move || {
+        assert_eq!(&a.a_u32 as *const u32 as usize, addr);
+        assert!(a.validate().is_ok());
+        assert_eq!(a.referenced_u32(), Some(42));
+        a
+    })
+    .join()
+    .unwrap();
+    assert!(ptr::eq(a.link_ptr().unwrap().as_ptr(), &a.a_u32));
+}
+
+#[test]
+fn shared_sync_is_read_from_many_threads() {
+    let a = AstructSharedSync::new_linked(1, 7u32);
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let a = a.clone();
+            thread::spawn(move || a.referenced_u32())
+        })
+        .collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), Some(7));
+    }
+    // Shared by reference as well
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(a.referenced_u32(), Some(7)));
+    });
+}
+
+#[test]
+fn pinned_xstruct_is_read_on_another_thread() {
+    let x = Xstruct::new_boxed(5);
+    let x = thread::spawn(move || {
+        assert_eq!(*x.as_ref().p(), 5);
+        x
+    })
+    .join()
+    .unwrap();
+    assert!(ptr::eq(x.as_ref().p(), x.link_ptr().as_ptr()));
+    assert_eq!(x.validate(), Ok(()));
+}